@@ -0,0 +1,99 @@
+//! Classifies synthetic branch and PR sets in memory. Half of the branches
+//! are merged, a quarter diverged from their PR and the rest have none.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use merged_branches_cli::{classify, evaluate, parse_branch, Options, RemoteBranch, RemoteCache};
+
+const NOW: u64 = 1_700_000_000;
+
+fn sha(seed: usize) -> String {
+    format!("{:040x}", seed)
+}
+
+fn synthetic(branches: usize) -> (Vec<merged_branches_cli::Branch>, RemoteCache) {
+    let mut local = vec![];
+    let mut pull_requests = vec![];
+    for index in 0..branches {
+        let name = format!("feature/{}", index);
+        local.push(format!("{} {} {}", name, sha(index), 1_600_000_000 + index));
+        match index % 4 {
+            0 | 1 => pull_requests.push(format!(
+                "merged #{} {} {} - master Merged",
+                index,
+                name,
+                sha(index)
+            )),
+            2 => pull_requests.push(format!(
+                "merged #{} {} {} - master Diverged",
+                index,
+                name,
+                sha(index + branches)
+            )),
+            _ => {}
+        }
+    }
+    let mut cache = RemoteCache::default();
+    cache.add_pull_requests(
+        pull_requests
+            .into_iter()
+            .filter_map(RemoteBranch::parse_line),
+        &["merged".to_string()],
+    );
+    (local.into_iter().filter_map(parse_branch).collect(), cache)
+}
+
+fn options(args: &[&str]) -> Options {
+    Options::parse(args.iter().map(|arg| arg.to_string())).expect("Invalid bench options")
+}
+
+fn bench_classify(c: &mut Criterion) {
+    let options = options(&["--max-age-warn", "30"]);
+    let mut group = c.benchmark_group("classify");
+    for branches in [100, 1_000, 10_000] {
+        let (local, cache) = synthetic(branches);
+        group.bench_with_input(BenchmarkId::from_parameter(branches), &local, |b, local| {
+            b.iter(|| {
+                for branch in local {
+                    classify(
+                        branch,
+                        &cache.by_hash,
+                        &cache.by_name,
+                        &options,
+                        NOW,
+                        |_, _| false,
+                    );
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    let options = options(&["--protect", "release/*", "--never-merged-commit", &sha(4)]);
+    let protected = vec!["release/*".to_string()];
+    let mut group = c.benchmark_group("evaluate");
+    for branches in [100, 1_000, 10_000] {
+        let (local, cache) = synthetic(branches);
+        group.bench_with_input(BenchmarkId::from_parameter(branches), &local, |b, local| {
+            b.iter(|| {
+                for branch in local {
+                    evaluate(
+                        branch,
+                        &cache.by_hash,
+                        &cache.by_name,
+                        &protected,
+                        &options,
+                        NOW,
+                        |_, _| false,
+                    )
+                    .expect("Can't evaluate");
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_classify, bench_evaluate);
+criterion_main!(benches);
@@ -0,0 +1,3439 @@
+use colored::*;
+use log::{debug, error, info, trace, warn};
+use std::io::*;
+use std::process::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Children that may still be streaming output, so Ctrl-C can kill them
+static RUNNING_CHILDREN: std::sync::Mutex<Vec<Child>> = std::sync::Mutex::new(Vec::new());
+/// Every command we build is run once, so this counts subprocesses for `--stats`
+static SUBPROCESSES: AtomicUsize = AtomicUsize::new(0);
+/// Lines of branches and PRs parsed, from git, the provider or input files
+static LINES_PARSED: AtomicUsize = AtomicUsize::new(0);
+
+const DEFAULT_KEEP_FILE: &str = ".merged-branches-keep";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DEFAULT_LIMIT: usize = 20;
+/// git doesn't abbreviate commits to fewer characters, and shorter prefixes
+/// would match far too much
+const MIN_COMMIT_PREFIX: usize = 4;
+/// Text output is flushed after this many lines
+const FLUSH_EVERY: usize = 8;
+/// Hash length when `core.abbrev` isn't set, like git's own default
+const DEFAULT_ABBREV: usize = 7;
+/// Log progress every this many branches in verbose mode
+const DEFAULT_PROGRESS_EVERY: usize = 100;
+/// `--remote-limit-auto` never asks the provider for more PRs than this
+const AUTO_LIMIT_CAP: usize = 1000;
+
+#[derive(Debug)]
+pub struct Options {
+    /// `-v` for info, `-vv` for debug, `-vvv` for trace
+    verbosity: u8,
+    /// Run `merged_branches doctor` instead of looking for merged branches
+    doctor: bool,
+    /// Only print results and errors
+    quiet: bool,
+    delete: bool,
+    /// Only delete branches whose merged PR has one of these labels
+    delete_if_label: Vec<String>,
+    /// Don't ask the provider or the remote about anything
+    no_remote: bool,
+    /// Count branches reachable from these as merged, without a PR
+    merged_into: Vec<String>,
+    /// Delete with this shell command instead of `git branch -D {name}`
+    delete_command: Option<String>,
+    /// Write the commands that recreate deleted branches here instead of stderr
+    restore_script: Option<String>,
+    /// Also delete the branch on `remote` after deleting it locally
+    delete_remote: bool,
+    /// Don't confirm local deletions
+    yes: bool,
+    /// Don't confirm remote deletions
+    yes_remote: bool,
+    /// Delete even when the branch has commits its remote doesn't
+    force: bool,
+    max_age_warn: Option<u64>,
+    /// How many PRs to fetch. 0 means no limit
+    limit: usize,
+    remote_limit_auto: bool,
+    group_by: Option<GroupBy>,
+    format: Format,
+    /// Detected from the remote's URL when not given
+    provider: Option<Provider>,
+    /// Length of abbreviated hashes, `core.abbrev` otherwise
+    abbrev: Option<usize>,
+    /// Show how far every branch is ahead of and behind this ref
+    merge_base_with: Option<String>,
+    /// Only show how this branch was classified
+    explain: Option<String>,
+    /// Log progress every this many branches. 0 disables it
+    progress_every: usize,
+    /// Show every branch on one line with its status, in the text format
+    oneline: bool,
+    /// Mention open PRs from the same branch next to merged branches
+    annotate_open_pr: bool,
+    sort_by: SortBy,
+    reverse: bool,
+    /// Pass this variable to the provider as its token
+    token_env: Option<String>,
+    /// PR states that count as merged, the provider's own otherwise
+    merged_states: Option<Vec<String>>,
+    /// The remote to prune tracking refs from
+    remote: String,
+    /// Use each branch's upstream remote instead of `remote` when deleting
+    default_remote_from_upstream: bool,
+    prune_tracking: bool,
+    /// Check this many of the latest commits of unmatched branches
+    match_history: Option<usize>,
+    /// Ignore local branches that don't match this glob
+    scope: Option<String>,
+    /// Globs of branches that are never listed or deleted
+    protect: Vec<String>,
+    /// Read more globs to protect from this file, `DEFAULT_KEEP_FILE` otherwise
+    keep_file: Option<String>,
+    /// Branches pointing at these commits are never merged, whatever the PRs say
+    never_merged_commits: Vec<String>,
+    /// Read more commits for `never_merged_commits` from this file
+    never_merged_file: Option<String>,
+    /// Keep this many of the newest branches for each of `prefixes`
+    keep_latest: Option<usize>,
+    prefixes: Vec<String>,
+    /// Exit with 1 when a local branch matching one of these globs isn't merged
+    fail_on_unmerged: Vec<String>,
+    /// Read PRs from this file instead of asking `hub`
+    pr_input: Option<String>,
+    /// Read local branches from this file instead of asking git
+    local_input: Option<String>,
+    /// Never spawn anything besides local git reads
+    offline: bool,
+    /// Print the number of merged branches instead of their names
+    count_only: bool,
+    /// Count protected branches that are merged too
+    count_protected: bool,
+    /// Report subprocesses, parsed lines, cache sizes and timings to stderr
+    stats: bool,
+    /// Exit with 1 when no branch counts as merged
+    exit_code: bool,
+    /// Don't say so on stderr when no branch is merged
+    no_empty_message: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum GroupBy {
+    /// Group by how the branch was determined to be merged
+    Strategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortBy {
+    Name,
+    /// Oldest tip commit first
+    Age,
+    /// Lowest PR number first
+    Pr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// Names of merged branches, one per line
+    Text,
+    Csv,
+    /// Workflow commands, so results show up as annotations in GitHub Actions
+    GitHubActions,
+    /// An array of objects with the `csv` fields, or an object with `--explain`
+    Json,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            verbosity: 0,
+            doctor: false,
+            quiet: false,
+            delete: false,
+            delete_if_label: vec![],
+            no_remote: false,
+            merged_into: vec![],
+            delete_command: None,
+            restore_script: None,
+            delete_remote: false,
+            yes: false,
+            yes_remote: false,
+            force: false,
+            max_age_warn: None,
+            limit: DEFAULT_LIMIT,
+            remote_limit_auto: false,
+            group_by: None,
+            format: Format::Text,
+            provider: None,
+            oneline: false,
+            annotate_open_pr: false,
+            sort_by: SortBy::Name,
+            reverse: false,
+            token_env: None,
+            merged_states: None,
+            progress_every: DEFAULT_PROGRESS_EVERY,
+            explain: None,
+            merge_base_with: None,
+            abbrev: None,
+            remote: "origin".to_string(),
+            default_remote_from_upstream: false,
+            prune_tracking: false,
+            match_history: None,
+            scope: None,
+            protect: vec![],
+            keep_file: None,
+            never_merged_commits: vec![],
+            never_merged_file: None,
+            keep_latest: None,
+            prefixes: vec![],
+            fail_on_unmerged: vec![],
+            pr_input: None,
+            local_input: None,
+            offline: false,
+            count_only: false,
+            stats: false,
+            count_protected: false,
+            exit_code: false,
+            no_empty_message: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> std::result::Result<Options, String> {
+        let mut options = Options::default();
+        let mut explicit_format = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "doctor" => options.doctor = true,
+                "--verbose" => options.verbosity = options.verbosity.saturating_add(1),
+                // -v, -vv, -vvv and so on
+                short
+                    if short.len() > 1
+                        && short.starts_with('-')
+                        && short[1..].bytes().all(|c| c == b'v') =>
+                {
+                    let count = (short.len() - 1).min(u8::MAX as usize) as u8;
+                    options.verbosity = options.verbosity.saturating_add(count);
+                }
+                "--quiet" | "-q" => options.quiet = true,
+                "--delete" => options.delete = true,
+                "--force" => options.force = true,
+                "--delete-remote" => options.delete_remote = true,
+                "--yes" | "-y" => options.yes = true,
+                "--yes-remote" => options.yes_remote = true,
+                "--remote-limit-auto" => options.remote_limit_auto = true,
+                "--offline" => options.offline = true,
+                "--no-remote" => options.no_remote = true,
+                "--oneline" => options.oneline = true,
+                "--annotate-open-pr" => options.annotate_open_pr = true,
+                "--reverse" => options.reverse = true,
+                "--sort-by" => {
+                    let value = args.next().ok_or("--sort-by requires a value")?;
+                    options.sort_by = match value.as_str() {
+                        "name" => SortBy::Name,
+                        "age" => SortBy::Age,
+                        "pr" => SortBy::Pr,
+                        _ => return Err(format!("Can't sort by {}, try name, age or pr", value)),
+                    };
+                }
+                "--default-remote-from-upstream" => options.default_remote_from_upstream = true,
+                "--prune-tracking" => options.prune_tracking = true,
+                "--count-only" => options.count_only = true,
+                "--stats" => options.stats = true,
+                "--count-protected" => options.count_protected = true,
+                "--exit-code" => options.exit_code = true,
+                "--no-empty-message" => options.no_empty_message = true,
+                "--group-by" => {
+                    let value = args.next().ok_or("--group-by requires a value")?;
+                    options.group_by = match value.as_str() {
+                        "strategy" => Some(GroupBy::Strategy),
+                        _ => return Err(format!("Can't group by {}, try `strategy`", value)),
+                    };
+                }
+                "--format" => {
+                    let value = args.next().ok_or("--format requires a value")?;
+                    options.format = match value.as_str() {
+                        "text" => Format::Text,
+                        "csv" => Format::Csv,
+                        "github-actions" => Format::GitHubActions,
+                        "json" => Format::Json,
+                        _ => {
+                            return Err(format!(
+                                "Unknown format {}, try text, csv, github-actions or json",
+                                value
+                            ))
+                        }
+                    };
+                    explicit_format = true;
+                }
+                "--provider-command" => {
+                    let command = args.next().ok_or("--provider-command requires a command")?;
+                    options.provider = Some(Provider::Command(command));
+                }
+                "--provider" => {
+                    let value = args.next().ok_or("--provider requires a value")?;
+                    options.provider = Some(value.parse()?);
+                }
+                "--remote" => {
+                    options.remote = args.next().ok_or("--remote requires a remote name")?;
+                }
+                "--match-history" => {
+                    let value = args.next().ok_or("--match-history requires a depth")?;
+                    let depth = value
+                        .parse()
+                        .map_err(|_| format!("Invalid depth: {}", value))?;
+                    options.match_history = Some(depth);
+                }
+                "--scope" => {
+                    let glob = args.next().ok_or("--scope requires a glob")?;
+                    options.scope = Some(glob);
+                }
+                "--abbrev" => {
+                    let value = args.next().ok_or("--abbrev requires a number")?;
+                    options.abbrev = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid hash length: {}", value))?,
+                    );
+                }
+                "--merge-base-with" => {
+                    let reference = args.next().ok_or("--merge-base-with requires a ref")?;
+                    options.merge_base_with = Some(reference);
+                }
+                "--explain" => {
+                    let branch = args.next().ok_or("--explain requires a branch")?;
+                    options.explain = Some(branch);
+                }
+                "--progress-every" => {
+                    let value = args.next().ok_or("--progress-every requires a number")?;
+                    options.progress_every = value
+                        .parse()
+                        .map_err(|_| format!("Invalid progress interval: {}", value))?;
+                }
+                "--token-env" => {
+                    let name = args.next().ok_or("--token-env requires a variable name")?;
+                    options.token_env = Some(name);
+                }
+                "--pr-state-field" => {
+                    let state = args.next().ok_or("--pr-state-field requires a state")?;
+                    options
+                        .merged_states
+                        .get_or_insert_with(Vec::new)
+                        .push(state);
+                }
+                "--delete-command" => {
+                    let template = args.next().ok_or("--delete-command requires a command")?;
+                    if !template.contains("{name}") {
+                        return Err(format!(
+                            "--delete-command needs a {{name}} placeholder for the branch, e.g. \"git branch -D {{name}}\", got {}",
+                            template
+                        ));
+                    }
+                    options.delete_command = Some(template);
+                }
+                "--restore-script" => {
+                    let path = args.next().ok_or("--restore-script requires a file path")?;
+                    options.restore_script = Some(path);
+                }
+                "--merged-into" => {
+                    let branch = args.next().ok_or("--merged-into requires a branch")?;
+                    options.merged_into.push(branch);
+                }
+                "--delete-if-label" => {
+                    let label = args.next().ok_or("--delete-if-label requires a label")?;
+                    options.delete_if_label.push(label);
+                }
+                "--protect" => {
+                    let glob = args.next().ok_or("--protect requires a glob")?;
+                    options.protect.push(glob);
+                }
+                "--keep-latest" => {
+                    let value = args.next().ok_or("--keep-latest requires a number")?;
+                    options.keep_latest =
+                        Some(value.parse().map_err(|_| {
+                            format!("Invalid number of branches to keep: {}", value)
+                        })?);
+                }
+                "--prefix" => {
+                    let prefix = args.next().ok_or("--prefix requires a branch prefix")?;
+                    options.prefixes.push(prefix);
+                }
+                "--never-merged-commit" => {
+                    let commit = args
+                        .next()
+                        .ok_or("--never-merged-commit requires a commit")?;
+                    if commit.len() < MIN_COMMIT_PREFIX {
+                        return Err(format!(
+                            "--never-merged-commit needs at least {} characters of the commit, got {}",
+                            MIN_COMMIT_PREFIX, commit
+                        ));
+                    }
+                    if !is_hex(&commit) {
+                        return Err(format!(
+                            "--never-merged-commit needs a commit hash, got {}",
+                            commit
+                        ));
+                    }
+                    // git prints hashes in lowercase, and they're compared as text
+                    options
+                        .never_merged_commits
+                        .push(commit.to_ascii_lowercase());
+                }
+                "--never-merged-file" => {
+                    let path = args
+                        .next()
+                        .ok_or("--never-merged-file requires a file path")?;
+                    options.never_merged_file = Some(path);
+                }
+                "--keep-file" => {
+                    let path = args.next().ok_or("--keep-file requires a file path")?;
+                    options.keep_file = Some(path);
+                }
+                "--fail-on-unmerged" => {
+                    let glob = args.next().ok_or("--fail-on-unmerged requires a glob")?;
+                    options.fail_on_unmerged.push(glob);
+                }
+                "--local-input" => {
+                    let path = args.next().ok_or("--local-input requires a file path")?;
+                    options.local_input = Some(path);
+                }
+                "--pr-input" => {
+                    let path = args.next().ok_or("--pr-input requires a file path")?;
+                    options.pr_input = Some(path);
+                }
+                "--limit" => {
+                    let value = args.next().ok_or("--limit requires a number")?;
+                    options.limit = value
+                        .parse()
+                        .map_err(|_| format!("Invalid limit: {}", value))?;
+                }
+                "--max-age-warn" => {
+                    let value = args
+                        .next()
+                        .ok_or("--max-age-warn requires a number of days")?;
+                    let days = value
+                        .parse()
+                        .map_err(|_| format!("Invalid number of days: {}", value))?;
+                    options.max_age_warn = Some(days);
+                }
+                other => return Err(format!("Unknown argument: {}", other)),
+            }
+        }
+        // Both of these only work with the text list
+        if !explicit_format
+            && !options.oneline
+            && !options.annotate_open_pr
+            && std::env::var("GITHUB_ACTIONS").as_deref() == Ok("true")
+        {
+            options.format = Format::GitHubActions;
+        }
+        if options.oneline && options.format != Format::Text {
+            return Err("--oneline only works with --format text".to_string());
+        }
+        if options.annotate_open_pr && (options.format != Format::Text || options.oneline) {
+            return Err("--annotate-open-pr only works with the --format text list".to_string());
+        }
+        if options.explain.is_some() && (options.delete || options.count_only) {
+            return Err(
+                "--explain can't be used together with --delete or --count-only".to_string(),
+            );
+        }
+        if options.delete && options.count_only {
+            return Err("--count-only can't be used together with --delete".to_string());
+        }
+        if options.offline && options.pr_input.is_none() {
+            return Err("--offline can't fetch PRs from GitHub, pass them with --pr-input".into());
+        }
+        if options.offline && options.prune_tracking {
+            return Err(
+                "--prune-tracking needs to reach the remote, so it can't run --offline".into(),
+            );
+        }
+        if options.no_remote && options.merged_into.is_empty() {
+            return Err(
+                "--no-remote needs a local way to detect merged branches, try --merged-into <branch>"
+                    .into(),
+            );
+        }
+        if options.no_remote && (options.pr_input.is_some() || options.provider.is_some()) {
+            return Err(
+                "--no-remote doesn't read PRs, so it can't be used with --pr-input or --provider"
+                    .into(),
+            );
+        }
+        if options.no_remote && (options.delete_remote || options.prune_tracking) {
+            return Err("--delete-remote and --prune-tracking need to reach the remote, so they can't be used with --no-remote".into());
+        }
+        if !options.delete_if_label.is_empty() && !options.delete {
+            return Err("--delete-if-label only works together with --delete".into());
+        }
+        if options.keep_latest.is_some() == options.prefixes.is_empty() {
+            return Err("--keep-latest and --prefix only work together".into());
+        }
+        if options.delete_command.is_some() && !options.delete {
+            return Err("--delete-command only works together with --delete".into());
+        }
+        if options.restore_script.is_some() && !options.delete {
+            return Err("--restore-script only works together with --delete".into());
+        }
+        if options.delete_remote && !options.delete {
+            return Err("--delete-remote only works together with --delete".into());
+        }
+        if options.offline && options.delete_remote {
+            return Err(
+                "--delete-remote needs to reach the remote, so it can't run --offline".into(),
+            );
+        }
+        if options.merge_base_with.is_some() && options.local_input.is_some() {
+            return Err("--merge-base-with can't compare branches read from --local-input".into());
+        }
+        if options.delete && options.local_input.is_some() {
+            return Err("--delete can't delete branches read from --local-input".into());
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    name: String,
+    commit_hash: String,
+    committed_at: Option<u64>,
+    /// The tip's commit date in ISO 8601
+    committed_date: Option<String>,
+    /// The tip's author
+    author: Option<String>,
+    pull_request: Option<PullRequest>,
+    /// For PR entries, whether `commit_hash` is the PR's merge commit instead of its head
+    merge_commit: bool,
+    /// Relative to `--merge-base-with`
+    divergence: Option<Divergence>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Divergence {
+    ahead: usize,
+    behind: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PullRequest {
+    number: String,
+    title: String,
+    /// The branch the PR was merged into
+    base: String,
+    labels: Vec<String>,
+}
+
+impl PullRequest {
+    /// Labels are case-insensitive on GitHub
+    fn has_label(&self, label: &str) -> bool {
+        self.labels
+            .iter()
+            .any(|pr_label| pr_label.eq_ignore_ascii_case(label))
+    }
+}
+
+pub fn parse_branch(line: String) -> Option<Branch> {
+    LINES_PARSED.fetch_add(1, Ordering::Relaxed);
+    // The author is last since it's the only field that can contain spaces
+    let parts: Vec<&str> = line.splitn(5, " ").collect();
+    let (name, commit_hash, committed_at, committed_date, author) = match parts.as_slice() {
+        [name, commit_hash] => (name, commit_hash, None, None, None),
+        [name, commit_hash, committed_at] => (name, commit_hash, Some(*committed_at), None, None),
+        [name, commit_hash, committed_at, committed_date] => (
+            name,
+            commit_hash,
+            Some(*committed_at),
+            Some(*committed_date),
+            None,
+        ),
+        [name, commit_hash, committed_at, committed_date, author] => (
+            name,
+            commit_hash,
+            Some(*committed_at),
+            Some(*committed_date),
+            Some(*author),
+        ),
+        _ => return None,
+    };
+    if is_pseudo_branch(name) {
+        return None;
+    }
+    Some(Branch {
+        name: name.to_string(),
+        commit_hash: commit_hash.to_string(),
+        committed_at: committed_at.and_then(|committed_at| committed_at.parse().ok()),
+        committed_date: committed_date.map(|date| date.to_string()),
+        author: author.map(|author| author.to_string()),
+        pull_request: None,
+        merge_commit: false,
+        divergence: None,
+    })
+}
+
+pub struct RemoteBranch {
+    state: String,
+    number: String,
+    name: String,
+    commit_hash: String,
+    /// The merge or squash commit, when the provider knows it
+    merge_commit_hash: Option<String>,
+    base: String,
+    title: String,
+    labels: Vec<String>,
+}
+
+impl RemoteBranch {
+    /// Parses `<state> <number> <branch> <head sha> [<merge sha> [<base> [<title>[\t<labels>]]]]`.
+    /// The merge sha can be empty or `-` for PRs that weren't merged. Labels are
+    /// separated by commas, after a tab so titles can still contain anything else.
+    pub fn parse_line(line: String) -> Option<RemoteBranch> {
+        LINES_PARSED.fetch_add(1, Ordering::Relaxed);
+        // The title is last since it's the only field that can contain spaces
+        let mut parts = line.splitn(7, " ");
+        let state = parts.next()?;
+        let number = parts.next()?;
+        let branch_name = parts.next()?;
+        let commit_hash = parts.next()?;
+        let merge_commit_hash = parts.next().filter(|hash| !hash.is_empty() && *hash != "-");
+        let base = parts.next().unwrap_or("");
+        let rest = parts.next().unwrap_or("");
+        let (title, labels) = rest.split_once('\t').unwrap_or((rest, ""));
+        Some(RemoteBranch {
+            state: state.to_string(),
+            number: number.to_string(),
+            name: branch_name.to_string(),
+            commit_hash: commit_hash.to_string(),
+            merge_commit_hash: merge_commit_hash.map(|hash| hash.to_string()),
+            base: base.to_string(),
+            title: title.to_string(),
+            labels: labels
+                .split(',')
+                .map(|label| label.trim().to_string())
+                .filter(|label| !label.is_empty())
+                .collect(),
+        })
+    }
+
+    pub fn branch(&self) -> Branch {
+        Branch {
+            name: self.name.to_string(),
+            commit_hash: self.commit_hash.to_string(),
+            committed_at: None,
+            committed_date: None,
+            author: None,
+            pull_request: Some(PullRequest {
+                number: self.number.to_string(),
+                title: self.title.to_string(),
+                base: self.base.to_string(),
+                labels: self.labels.clone(),
+            }),
+            merge_commit: false,
+            divergence: None,
+        }
+    }
+
+    /// The PR's head, and its merge commit when it's a different one. After a
+    /// squash merge, a local branch can point at either of them.
+    pub fn branches(&self) -> Vec<Branch> {
+        let head = self.branch();
+        match &self.merge_commit_hash {
+            Some(merge_commit_hash) if *merge_commit_hash != self.commit_hash => {
+                let merge = Branch {
+                    commit_hash: merge_commit_hash.to_string(),
+                    merge_commit: true,
+                    ..head.clone()
+                };
+                vec![head, merge]
+            }
+            _ => vec![head],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Provider {
+    Hub,
+    Gh,
+    GitLab,
+    Bitbucket,
+    /// `--provider-command`: a shell command printing PRs in our line format
+    Command(String),
+}
+
+/// `major.minor.patch`
+type Version = (u32, u32, u32);
+
+const MIN_HUB_VERSION: Version = (2, 3, 0);
+/// The first hub that knows `%sm`, the merge commit
+const HUB_MERGE_SHA_VERSION: Version = (2, 12, 0);
+/// The first gh with `pr list --json`
+const MIN_GH_VERSION: Version = (2, 0, 0);
+
+/// Finds `<program> version 1.2.3` in `--version` output. hub prints git's
+/// version first, so the program name matters
+fn parse_version(output: &str, program: &str) -> Option<Version> {
+    let prefix = format!("{} version ", program);
+    let version = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(prefix.as_str()))?;
+    let mut numbers = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .map(|number| number.parse().ok());
+    let major = numbers.next()??;
+    let minor = numbers.next().flatten().unwrap_or(0);
+    let patch = numbers.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+/// Runs `hub --version` or `gh --version` once per run and provider, warning
+/// when it's too old
+fn provider_version(provider: &Provider) -> Option<Version> {
+    static HUB_VERSION: std::sync::OnceLock<Option<Version>> = std::sync::OnceLock::new();
+    static GH_VERSION: std::sync::OnceLock<Option<Version>> = std::sync::OnceLock::new();
+    let (version, minimum) = match provider {
+        Provider::Hub => (&HUB_VERSION, MIN_HUB_VERSION),
+        Provider::Gh => (&GH_VERSION, MIN_GH_VERSION),
+        _ => return None,
+    };
+    *version.get_or_init(|| {
+        let program = provider.to_string();
+        let output = new_command(&program)
+            .arg("--version")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let version = parse_version(&String::from_utf8_lossy(&output.stdout), &program);
+        match version {
+            Some(version) if version < minimum => warn!(
+                "{} {}.{}.{} is older than {}.{}.{}, which is the oldest supported version. PRs might not be read correctly",
+                program, version.0, version.1, version.2, minimum.0, minimum.1, minimum.2
+            ),
+            Some(version) => debug!(
+                "> Using {} {}.{}.{}",
+                program, version.0, version.1, version.2
+            ),
+            None => debug!("> Can't tell which version of {} this is", program),
+        }
+        version
+    })
+}
+
+/// Older hubs print unknown placeholders as they are, so they get a `-` for
+/// the merge commit instead of `%sm`. Unknown versions are assumed to be recent
+fn hub_format(version: Option<Version>) -> &'static str {
+    match version {
+        Some(version) if version < HUB_MERGE_SHA_VERSION => "%S %i %H %sH - %B %t\t%L%n",
+        _ => "%S %i %H %sH %sm %B %t\t%L%n",
+    }
+}
+
+/// Token variables every provider CLI we know of reads
+const TOKEN_VARS: [&str; 3] = ["GITHUB_TOKEN", "GH_TOKEN", "GITLAB_TOKEN"];
+
+impl Provider {
+    /// The states this provider reports for PRs that aren't open anymore.
+    /// Closed PRs count as merged, since their branch is done with either way
+    fn merged_states(&self) -> Vec<String> {
+        let states: &[&str] = match self {
+            Provider::Hub | Provider::GitLab | Provider::Command(_) => &["merged", "closed"],
+            Provider::Gh => &["MERGED", "CLOSED"],
+            Provider::Bitbucket => &["MERGED", "DECLINED", "SUPERSEDED"],
+        };
+        states.iter().map(|state| state.to_string()).collect()
+    }
+
+    /// Where this provider's CLI looks for a token
+    fn token_vars(&self) -> &'static [&'static str] {
+        match self {
+            Provider::Hub => &["GITHUB_TOKEN"],
+            Provider::Gh => &["GH_TOKEN", "GITHUB_TOKEN"],
+            Provider::GitLab => &["GITLAB_TOKEN"],
+            Provider::Bitbucket => &[],
+            Provider::Command(_) => &TOKEN_VARS,
+        }
+    }
+
+    /// The token variables of this provider's forge, which can stand in for
+    /// each other without `--token-env`. A GitHub token never goes to GitLab
+    fn forge_token_vars(&self) -> &'static [&'static str] {
+        match self {
+            Provider::Hub | Provider::Gh | Provider::Command(_) => &["GITHUB_TOKEN", "GH_TOKEN"],
+            Provider::GitLab => &["GITLAB_TOKEN"],
+            Provider::Bitbucket => &[],
+        }
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Provider, String> {
+        match value {
+            "hub" => Ok(Provider::Hub),
+            "gh" => Ok(Provider::Gh),
+            "gitlab" => Ok(Provider::GitLab),
+            "bitbucket" => Ok(Provider::Bitbucket),
+            _ => Err(format!(
+                "Unknown provider {}, try hub, gh, gitlab or bitbucket",
+                value
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Provider::Hub => write!(f, "hub"),
+            Provider::Gh => write!(f, "gh"),
+            Provider::GitLab => write!(f, "gitlab"),
+            Provider::Bitbucket => write!(f, "bitbucket"),
+            Provider::Command(command) => write!(f, "{}", command),
+        }
+    }
+}
+
+/// Extracts the host from `git@host:owner/repo.git`, `ssh://git@host:22/owner/repo`
+/// and `https://user@host/owner/repo.git` style URLs
+fn remote_host(url: &str) -> Option<&str> {
+    let authority = match url.find("://") {
+        // `user:password@host:port`, up to the path
+        Some(index) => url[index + 3..].split('/').next()?,
+        // `user@host:path`
+        None if url.contains(':') => url.split(':').next()?,
+        None => return None,
+    };
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// `host_mapping` gets the provider configured for a self-hosted host
+fn provider_for_url<F: Fn(&str) -> Option<Provider>>(
+    url: &str,
+    host_mapping: F,
+) -> Option<Provider> {
+    let host = remote_host(url)?;
+    if let Some(provider) = host_mapping(host) {
+        return Some(provider);
+    }
+    match host {
+        "github.com" | "ssh.github.com" => Some(Provider::Hub),
+        "bitbucket.org" => Some(Provider::Bitbucket),
+        host if host == "gitlab.com" || host.starts_with("gitlab.") => Some(Provider::GitLab),
+        _ => None,
+    }
+}
+
+/// Reads `git config merged-branches.<host>.provider`
+fn configured_provider(host: &str) -> Option<Provider> {
+    let output = new_command("git")
+        .args(vec![
+            "config",
+            "--get",
+            &format!("merged-branches.{}.provider", host),
+        ])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Picks the provider from the remote's URL, defaulting to `hub`
+fn detect_provider(remote: &str) -> Provider {
+    let url = new_command("git")
+        .args(vec!["remote", "get-url", remote])
+        .stderr(Stdio::null())
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    match provider_for_url(&url, configured_provider) {
+        Some(provider) => {
+            info!("> Detected {} from {}", provider, url);
+            provider
+        }
+        None => Provider::Hub,
+    }
+}
+
+fn get_pull_requests(
+    provider: &Provider,
+    limit: usize,
+    token_env: Option<&str>,
+) -> std::io::Result<impl Iterator<Item = RemoteBranch>> {
+    let limit = limit.to_string();
+    let mut command = match provider {
+        Provider::Command(provider_command) => shell(provider_command),
+        Provider::Hub => {
+            // hub pr list -s all -f "%S %i %H %sH %sm %B %t<tab>%L%n" --limit 20
+            let mut command = new_command("hub");
+            command.args(vec![
+                "pr",
+                "list",
+                "-s",
+                "all",
+                "-f",
+                hub_format(provider_version(provider)),
+            ]);
+            if limit != "0" {
+                command.args(vec!["--limit", &limit]);
+            }
+            command
+        }
+        Provider::Gh => {
+            provider_version(provider);
+            // Same line format as hub, so it goes through `RemoteBranch::parse_line`
+            let mut command = new_command("gh");
+            command.args(vec![
+                "pr",
+                "list",
+                "--state",
+                "all",
+                "--json",
+                "state,number,headRefName,headRefOid,mergeCommit,baseRefName,title,labels",
+                "--template",
+                "{{range .}}{{.state}} #{{.number}} {{.headRefName}} {{.headRefOid}} {{if .mergeCommit}}{{.mergeCommit.oid}}{{else}}-{{end}} {{.baseRefName}} {{.title}}\t{{range $i, $label := .labels}}{{if $i}},{{end}}{{$label.name}}{{end}}{{\"\\n\"}}{{end}}",
+                "--limit",
+                if limit == "0" { "9999" } else { &limit },
+            ]);
+            command
+        }
+        Provider::GitLab | Provider::Bitbucket => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{} isn't supported yet", provider),
+            ));
+        }
+    };
+    forward_token(&mut command, provider, token_env);
+    let git_branch = command.stdout(std::process::Stdio::piped()).spawn()?;
+    let git_branches = BufReader::new(track_child(git_branch)).lines();
+    Ok(git_branches.filter_map(|line| RemoteBranch::parse_line(line.ok()?)))
+}
+
+/// Sets the provider's token variables on `command` explicitly, from `token_env`
+/// or whichever token variable of the same forge is set, so CI tokens reach
+/// CLIs that only read one of them. Only variable names are logged, never
+/// their values
+fn forward_token(command: &mut Command, provider: &Provider, token_env: Option<&str>) {
+    forward_token_from(command, provider, token_env, |name| {
+        std::env::var(name).ok()
+    })
+}
+
+/// `forward_token`, reading variables with `env`
+fn forward_token_from<E: Fn(&str) -> Option<String>>(
+    command: &mut Command,
+    provider: &Provider,
+    token_env: Option<&str>,
+    env: E,
+) {
+    let source = match token_env {
+        // An empty token is as good as none, and would only hide the CLI's own login
+        Some(name) => match env(name) {
+            Some(token) if !token.is_empty() => Some((name.to_string(), token)),
+            _ => {
+                warn!(
+                    "${} isn't set or is empty, so no token is passed to {}",
+                    name, provider
+                );
+                None
+            }
+        },
+        None => provider.forge_token_vars().iter().find_map(|name| {
+            env(name)
+                .filter(|token| !token.is_empty())
+                .map(|token| (name.to_string(), token))
+        }),
+    };
+    let (name, token) = match source {
+        Some(source) => source,
+        None => return,
+    };
+    for var in provider.token_vars() {
+        if token_env.is_none() && !provider.forge_token_vars().contains(var) {
+            continue;
+        }
+        // The CLI reads a variable that's set already by itself, unless
+        // `--token-env` picked a different one
+        let is_set = env(var).is_some_and(|value| !value.is_empty());
+        if is_set && token_env.is_none() {
+            continue;
+        }
+        debug!("> Passing ${} to {} as ${}", name, provider, var);
+        command.env(var, &token);
+    }
+}
+
+/// `Command::new`, counted for `--stats`
+fn new_command<S: AsRef<std::ffi::OsStr>>(program: S) -> Command {
+    SUBPROCESSES.fetch_add(1, Ordering::Relaxed);
+    Command::new(program)
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = new_command("cmd");
+        shell.args(vec!["/C", command]);
+        shell
+    } else {
+        let mut shell = new_command("sh");
+        shell.args(vec!["-c", command]);
+        shell
+    }
+}
+
+/// Reads PRs in the same format `hub pr list -f "%S %i %H %sH %sm %B %t<tab>%L%n"` prints
+fn read_pull_requests(path: &str) -> std::io::Result<impl Iterator<Item = RemoteBranch>> {
+    let file = std::fs::File::open(path)?;
+    let lines = BufReader::new(file).lines();
+    Ok(lines.filter_map(|line| RemoteBranch::parse_line(line.ok()?)))
+}
+
+/// Providers disagree on casing, so `merged_states` are compared case-insensitively
+fn is_merged_state(state: &str, merged_states: &[String]) -> bool {
+    merged_states
+        .iter()
+        .any(|merged_state| merged_state.eq_ignore_ascii_case(state))
+}
+
+/// `open` for hub and gh, `opened` for GitLab
+fn is_open_state(state: &str) -> bool {
+    state.eq_ignore_ascii_case("open") || state.eq_ignore_ascii_case("opened")
+}
+
+/// What merged PRs point at. It's filled while the provider's lines come in,
+/// so there's nothing left to index once both sides are collected
+#[derive(Default)]
+pub struct RemoteCache {
+    pub by_hash: std::collections::HashMap<String, Vec<Branch>>,
+    /// Only the entries that came from a PR
+    pub by_name: std::collections::HashMap<String, Vec<Branch>>,
+    /// Open PRs, by their branch's name
+    pub open: std::collections::HashMap<String, Vec<PullRequest>>,
+}
+
+impl RemoteCache {
+    /// Merged PRs are indexed like any other branch. Open ones are only kept
+    /// to tell that a branch is still in use, and the rest are dropped
+    pub fn add_pull_requests(
+        &mut self,
+        pull_requests: impl IntoIterator<Item = RemoteBranch>,
+        merged_states: &[String],
+    ) {
+        for remote_branch in pull_requests {
+            if is_merged_state(&remote_branch.state, merged_states) {
+                self.extend(remote_branch.branches());
+            } else if is_open_state(&remote_branch.state) {
+                let pull_request = remote_branch.branch().pull_request;
+                self.open
+                    .entry(remote_branch.name)
+                    .or_default()
+                    .extend(pull_request);
+            }
+        }
+    }
+}
+
+impl Extend<Branch> for RemoteCache {
+    fn extend<I: IntoIterator<Item = Branch>>(&mut self, branches: I) {
+        for branch in branches {
+            if branch.pull_request.is_some() {
+                self.by_name
+                    .entry(branch.name.to_string())
+                    .or_default()
+                    .push(branch.clone());
+            }
+            self.by_hash
+                .entry(branch.commit_hash.to_string())
+                .or_default()
+                .push(branch);
+        }
+    }
+}
+
+/// When the provider returns exactly `limit` PRs there are probably more,
+/// so branches we couldn't match might still be merged
+fn saturation_warning(fetched: usize, limit: usize) -> Option<String> {
+    if limit == 0 || fetched != limit {
+        return None;
+    }
+    Some(format!(
+        "Got exactly {} PRs, which is the limit. Older merged PRs weren't fetched, so some merged branches may be missing. Try a higher --limit, or --limit 0 for no limit",
+        limit
+    ))
+}
+
+fn warn_if_saturated(fetched: usize, limit: usize) {
+    if let Some(warning) = saturation_warning(fetched, limit) {
+        warn!("{}", warning.bold());
+    }
+}
+
+fn get_remote_branches(
+    provider: &Provider,
+    limit: usize,
+    merged_states: &[String],
+    token_env: Option<&str>,
+) -> std::io::Result<RemoteCache> {
+    let mut fetched = 0;
+    let mut cache = RemoteCache::default();
+    let pull_requests = get_pull_requests(provider, limit, token_env)?.inspect(|_| fetched += 1);
+    cache.add_pull_requests(pull_requests, merged_states);
+    // Provider commands don't get the limit
+    if !matches!(provider, Provider::Command(_)) {
+        warn_if_saturated(fetched, limit);
+    }
+    Ok(cache)
+}
+
+/// Grows the limit until every one of `targets` is matched, the provider runs
+/// out of PRs, doubling matched nothing new, or we hit `AUTO_LIMIT_CAP`.
+/// Protected branches shouldn't be in `targets`, since they might never match
+fn get_remote_branches_auto(
+    provider: &Provider,
+    targets: &[&Branch],
+    mut limit: usize,
+    merged_states: &[String],
+    token_env: Option<&str>,
+) -> std::io::Result<RemoteCache> {
+    let mut previously_matched = None;
+    loop {
+        let mut fetched = 0;
+        let mut cache = RemoteCache::default();
+        let pull_requests =
+            get_pull_requests(provider, limit, token_env)?.inspect(|_| fetched += 1);
+        cache.add_pull_requests(pull_requests, merged_states);
+        let matched = targets
+            .iter()
+            .filter(|branch| {
+                cache.by_hash.contains_key(&branch.commit_hash)
+                    || cache.by_name.contains_key(&branch.name)
+            })
+            .count();
+        info!(
+            "> Fetched {} PRs with --limit {}, matching {} of {} branches",
+            fetched,
+            limit,
+            matched,
+            targets.len()
+        );
+        if matched == targets.len() || fetched < limit {
+            return Ok(cache);
+        }
+        // The rest are probably not merged at all, like work in progress
+        if previously_matched == Some(matched) {
+            return Ok(cache);
+        }
+        previously_matched = Some(matched);
+        if limit >= AUTO_LIMIT_CAP {
+            warn_if_saturated(fetched, limit);
+            return Ok(cache);
+        }
+        limit = std::cmp::min(limit * 2, AUTO_LIMIT_CAP);
+    }
+}
+
+/// Falls back to offline detection when the provider CLI isn't installed
+fn or_merged_into_default(
+    provider: &Provider,
+    result: std::io::Result<RemoteCache>,
+) -> RemoteCache {
+    match result {
+        Ok(cache) => cache,
+        Err(ref err)
+            if err.kind() == ErrorKind::NotFound || err.kind() == ErrorKind::Unsupported =>
+        {
+            let default_branch = get_default_branch();
+            let reason = if err.kind() == ErrorKind::NotFound {
+                format!("`{}` is not installed", provider)
+            } else {
+                err.to_string()
+            };
+            warn!(
+                "{}, so PR-based detection is unavailable. Falling back to `git branch --merged {}`",
+                reason, default_branch
+            );
+            // The default branch itself is in there too, but it's never a candidate
+            let mut cache = RemoteCache::default();
+            cache.extend(get_merged_branches(&default_branch).expect("Can't get merged branches"));
+            cache
+        }
+        Err(err) => panic!("Can't get remote branches: {}", err),
+    }
+}
+
+/// Gets everything we need about the tips in a single `git branch` call,
+/// instead of running `git log` for every branch
+fn get_local_branches() -> std::io::Result<impl Iterator<Item = Branch>> {
+    let git_branch = new_command("git")
+        .arg("branch")
+        .arg("--format")
+        .arg("%(if)%(symref)%(then)(symref)%(else)%(refname:short)%(end) %(objectname) %(committerdate:unix) %(committerdate:iso-strict) %(authorname)")
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let git_branches = BufReader::new(track_child(git_branch)).lines();
+    let branches = git_branches.filter_map(|line| parse_branch(line.ok()?));
+    Ok(branches)
+}
+
+/// `HEAD`, `(HEAD detached at ...)`, `(no branch, rebasing ...)` and symbolic
+/// refs (which `get_local_branches` names `(symref)`) aren't branches of their own
+fn is_pseudo_branch(name: &str) -> bool {
+    name == "HEAD" || name.ends_with("/HEAD") || name.starts_with('(')
+}
+
+/// Reads branches in the same format `get_local_branches` asks git for
+fn read_local_branches(path: &str) -> std::io::Result<impl Iterator<Item = Branch>> {
+    let file = std::fs::File::open(path)?;
+    let lines = BufReader::new(file).lines();
+    Ok(lines.filter_map(|line| parse_branch(line.ok()?)))
+}
+
+/// Takes the child's stdout and keeps the child around for `on_interrupt`
+/// until its output is drained
+fn track_child(mut child: Child) -> TrackedStdout {
+    let stdout = child.stdout.take().expect("Child stdout isn't piped");
+    let id = child.id();
+    if let Ok(mut children) = RUNNING_CHILDREN.lock() {
+        children.push(child);
+    }
+    TrackedStdout { id, stdout }
+}
+
+/// A tracked child's stdout, which reaps the child once it's read to the end
+struct TrackedStdout {
+    id: u32,
+    stdout: ChildStdout,
+}
+
+impl Read for TrackedStdout {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            reap_child(self.id);
+        }
+        Ok(read)
+    }
+}
+
+/// Waits on a child whose output is done, so it doesn't linger as a zombie
+fn reap_child(id: u32) {
+    let mut child = match RUNNING_CHILDREN.lock() {
+        Ok(mut children) => match children.iter().position(|child| child.id() == id) {
+            Some(index) => children.swap_remove(index),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    let _ = child.wait();
+}
+
+/// Kills and reaps every tracked child, returning how many there were
+fn kill_children() -> usize {
+    let mut children = match RUNNING_CHILDREN.lock() {
+        Ok(mut children) => std::mem::take(&mut *children),
+        Err(_) => return 0,
+    };
+    for child in children.iter_mut() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    children.len()
+}
+
+/// Kills spawned children instead of orphaning them, and resets the
+/// terminal in case we were interrupted in the middle of colored output
+fn on_interrupt() {
+    kill_children();
+    if stderr().is_terminal() {
+        eprintln!("\x1b[0m");
+    }
+    exit(130);
+}
+
+/// Globs from the keep file, one per line. `#` starts a comment.
+/// A missing keep file is fine unless it was given with `--keep-file`.
+fn parse_keep_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn is_hex(commit: &str) -> bool {
+    commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Hashes can be abbreviated, down to `MIN_COMMIT_PREFIX` characters
+fn is_never_merged(commit_hash: &str, never_merged_commits: &[String]) -> bool {
+    never_merged_commits
+        .iter()
+        .any(|prefix| commit_hash.starts_with(prefix.as_str()))
+}
+
+/// `--never-merged-file`, in the same format as the keep file
+fn read_never_merged_file(path: &str) -> std::io::Result<Vec<String>> {
+    let commits = match std::fs::read_to_string(path) {
+        Ok(contents) => parse_keep_file(&contents),
+        Err(err) => {
+            return Err(Error::new(
+                err.kind(),
+                format!("Can't read {}: {}", path, err),
+            ))
+        }
+    };
+    if let Some(commit) = commits
+        .iter()
+        .find(|commit| commit.len() < MIN_COMMIT_PREFIX)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} needs at least {} characters of every commit, got {}",
+                path, MIN_COMMIT_PREFIX, commit
+            ),
+        ));
+    }
+    match commits.iter().find(|commit| !is_hex(commit)) {
+        Some(commit) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} needs commit hashes, got {}", path, commit),
+        )),
+        None => Ok(commits
+            .iter()
+            .map(|commit| commit.to_ascii_lowercase())
+            .collect()),
+    }
+}
+
+fn read_keep_file(options: &Options) -> std::io::Result<Vec<String>> {
+    let path = match &options.keep_file {
+        Some(path) => std::path::PathBuf::from(path),
+        None if options.local_input.is_some() => std::path::PathBuf::from(DEFAULT_KEEP_FILE),
+        None => repository_root().join(DEFAULT_KEEP_FILE),
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse_keep_file(&contents)),
+        Err(ref err) if err.kind() == ErrorKind::NotFound && options.keep_file.is_none() => {
+            Ok(vec![])
+        }
+        Err(err) => Err(Error::new(
+            err.kind(),
+            format!("Can't read {}: {}", path.display(), err),
+        )),
+    }
+}
+
+/// The git directory itself for bare repositories. Falls back to the current
+/// directory outside of a repository
+fn repository_root() -> std::path::PathBuf {
+    let root = if is_bare_repository() {
+        "--absolute-git-dir"
+    } else {
+        "--show-toplevel"
+    };
+    let output = new_command("git")
+        .args(vec!["rev-parse", root])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            std::path::PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+        }
+        _ => std::path::PathBuf::from("."),
+    }
+}
+
+/// `core.abbrev`, which is a length, `auto` or `no` for full hashes
+fn core_abbrev() -> Option<usize> {
+    let output = new_command("git")
+        .args(vec!["config", "--get", "core.abbrev"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_core_abbrev(String::from_utf8_lossy(&output.stdout).trim())
+}
+
+/// Like git, any false value means full hashes, and `auto` or anything
+/// unparsable leaves it to the default
+fn parse_core_abbrev(value: &str) -> Option<usize> {
+    match value.to_lowercase().as_str() {
+        "no" | "false" | "off" | "0" => Some(usize::MAX),
+        // git never abbreviates to fewer than 4 characters
+        value => value.parse().ok().map(|length: usize| length.max(4)),
+    }
+}
+
+fn abbreviate(commit_hash: &str, length: usize) -> &str {
+    &commit_hash[..std::cmp::min(length, commit_hash.len())]
+}
+
+fn is_bare_repository() -> bool {
+    let output = new_command("git")
+        .args(vec!["rev-parse", "--is-bare-repository"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim() == "true"
+        }
+        _ => false,
+    }
+}
+
+/// `None` on a detached HEAD
+fn current_branch() -> Option<String> {
+    let output = new_command("git")
+        .args(vec!["symbolic-ref", "--short", "--quiet", "HEAD"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Branches checked out in the main work tree or a linked one. The main entry
+/// of a bare repository has no branch
+fn checked_out_branches() -> Vec<String> {
+    let output = new_command("git")
+        .args(vec!["worktree", "list", "--porcelain"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            parse_worktree_branches(&String::from_utf8_lossy(&output.stdout))
+        }
+        // git before 2.7 has no `worktree list`
+        _ if is_bare_repository() => vec![],
+        _ => current_branch().into_iter().collect(),
+    }
+}
+
+/// Picks the `branch refs/heads/<name>` lines of `git worktree list --porcelain`
+fn parse_worktree_branches(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("branch refs/heads/"))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// `origin/HEAD`, like `origin/main`. Assumed to be `master` when it isn't set,
+/// which is worth a warning since a `main`-only repository has no `master`
+fn get_default_branch() -> String {
+    static DEFAULT_BRANCH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    DEFAULT_BRANCH
+        .get_or_init(|| {
+            let output = new_command("git")
+                .args(vec!["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])
+                .stderr(Stdio::null())
+                .output();
+            match output {
+                Ok(ref output) if output.status.success() => {
+                    String::from_utf8_lossy(&output.stdout).trim().to_string()
+                }
+                _ => {
+                    warn!("origin/HEAD isn't set, so the default branch is assumed to be master. Set it with `git remote set-head origin --auto`");
+                    "master".to_string()
+                }
+            }
+        })
+        .to_string()
+}
+
+/// The local branch `get_default_branch` refers to
+fn local_default_branch() -> String {
+    let default_branch = get_default_branch();
+    match default_branch.strip_prefix("origin/") {
+        Some(name) => name.to_string(),
+        None => default_branch,
+    }
+}
+
+/// Offline detection: branches whose tip is reachable from `base`.
+/// Used when there's no PR provider to ask.
+fn get_merged_branches(base: &str) -> std::io::Result<impl Iterator<Item = Branch>> {
+    let git_branch = new_command("git")
+        .arg("branch")
+        .arg("--merged")
+        .arg(base)
+        .arg("--format")
+        .arg("%(refname:short) %(objectname)")
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let git_branches = BufReader::new(track_child(git_branch)).lines();
+    let branches = git_branches.filter_map(|line| parse_branch(line.ok()?));
+    Ok(branches)
+}
+
+/// Parses `git rev-list --left-right --count <ref>...<branch>`, which counts the
+/// commits only `<ref>` has (behind) and the ones only `<branch>` has (ahead)
+fn parse_left_right_count(output: &str) -> Option<Divergence> {
+    let mut counts = output.split_whitespace();
+    let behind = counts.next()?.parse().ok()?;
+    let ahead = counts.next()?.parse().ok()?;
+    Some(Divergence { ahead, behind })
+}
+
+fn get_divergence(reference: &str, branch: &Branch) -> Option<Divergence> {
+    let output = new_command("git")
+        .args(vec![
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("{}...{}", reference, branch.commit_hash),
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_left_right_count(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Every local branch's divergence from `reference` with a single
+/// `git for-each-ref`. `None` when git is too old for `%(ahead-behind)`
+fn get_divergences(reference: &str) -> Option<std::collections::HashMap<String, Divergence>> {
+    let output = new_command("git")
+        .args(vec![
+            "for-each-ref",
+            &format!("--format=%(refname:short) %(ahead-behind:{})", reference),
+            "refs/heads",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(parse_ahead_behind)
+            .collect(),
+    )
+}
+
+/// Parses `<name> <ahead> <behind>`, as `%(refname:short) %(ahead-behind:<ref>)`
+/// prints it
+fn parse_ahead_behind(line: &str) -> Option<(String, Divergence)> {
+    let mut parts = line.split(' ');
+    let name = parts.next()?;
+    let ahead = parts.next()?.parse().ok()?;
+    let behind = parts.next()?.parse().ok()?;
+    Some((name.to_string(), Divergence { ahead, behind }))
+}
+
+/// `--merge-base-with`: batched when git supports it, one `rev-list` per branch otherwise
+fn add_divergences(reference: &str, branches: &mut [Branch]) {
+    match get_divergences(reference) {
+        Some(divergences) => {
+            for branch in branches.iter_mut() {
+                branch.divergence = divergences.get(&branch.name).copied();
+            }
+        }
+        None => {
+            debug!("> git can't batch ahead/behind counts, asking for each branch");
+            for branch in branches.iter_mut() {
+                branch.divergence = get_divergence(reference, branch);
+            }
+        }
+    }
+}
+
+/// `--merged-into`: local branches reachable from `base`, besides `base` itself
+fn merged_into(base: &str, remote: &str) -> std::io::Result<Vec<Branch>> {
+    let local_base = base.strip_prefix(&format!("{}/", remote)).unwrap_or(base);
+    Ok(get_merged_branches(base)?
+        .filter(|branch| branch.name != base && branch.name != local_base)
+        .collect())
+}
+
+/// Matches `*` (any run of characters, `/` included) and `?` (any single character)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume when the last `*` has to swallow one more character
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// How a branch was determined to be merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Via {
+    /// The branch tip is the head commit of a merged PR, or its merge commit
+    /// when the branch is the one the PR came from
+    ExactHash,
+    /// The branch tip is reachable from the default branch (`git branch --merged`),
+    /// or from the head of a merged PR with the same name
+    Ancestor,
+}
+
+impl std::fmt::Display for Via {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Via::ExactHash => write!(f, "exact-hash"),
+            Via::Ancestor => write!(f, "ancestor"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MergeStatus {
+    /// `pull_requests` has every merged PR pointing at the branch, which can
+    /// be more than one when it was merged into several bases
+    Merged {
+        via: Via,
+        pull_requests: Vec<PullRequest>,
+    },
+    /// Merged, and the tip commit is older than `--max-age-warn` days
+    StaleMerged {
+        age_days: u64,
+        via: Via,
+        pull_requests: Vec<PullRequest>,
+    },
+    /// A merged PR has the same branch name, but points at another commit.
+    /// Usually means there are commits that were added after merging.
+    Diverged {
+        pull_request: PullRequest,
+    },
+    /// `--match-history`: one of the branch's recent commits was merged, but
+    /// the branch has more commits on top of it
+    MergedWithCommitsOnTop {
+        commits_on_top: usize,
+        pull_request: Option<PullRequest>,
+    },
+    NotFound,
+    /// Matches `--protect` or the keep file, so it's never listed or deleted.
+    /// Wraps what the branch would have been classified as otherwise.
+    Protected(Box<MergeStatus>),
+}
+
+impl MergeStatus {
+    /// How the branch was merged, for branches that should be listed or deleted
+    pub fn via(&self) -> Option<Via> {
+        match self {
+            MergeStatus::Merged { via, .. } | MergeStatus::StaleMerged { via, .. } => Some(*via),
+            MergeStatus::Diverged { .. }
+            | MergeStatus::MergedWithCommitsOnTop { .. }
+            | MergeStatus::NotFound
+            | MergeStatus::Protected(_) => None,
+        }
+    }
+
+    /// Unlike `via`, this is true for protected branches that are merged too
+    pub fn is_merged(&self) -> bool {
+        match self {
+            MergeStatus::Merged { .. } | MergeStatus::StaleMerged { .. } => true,
+            MergeStatus::Diverged { .. }
+            | MergeStatus::MergedWithCommitsOnTop { .. }
+            | MergeStatus::NotFound => false,
+            MergeStatus::Protected(status) => status.is_merged(),
+        }
+    }
+
+    /// Whether the branch is included in the number of merged branches. Protected
+    /// branches are only counted when asked to, since they won't be deleted anyway
+    pub fn counts_as_merged(&self, count_protected: bool) -> bool {
+        match self {
+            MergeStatus::Protected(status) => count_protected && status.via().is_some(),
+            status => status.via().is_some(),
+        }
+    }
+
+    pub fn pull_request(&self) -> Option<&PullRequest> {
+        match self {
+            MergeStatus::Merged { pull_requests, .. }
+            | MergeStatus::StaleMerged { pull_requests, .. } => pull_requests.first(),
+            MergeStatus::Diverged { pull_request } => Some(pull_request),
+            MergeStatus::MergedWithCommitsOnTop { pull_request, .. } => pull_request.as_ref(),
+            MergeStatus::NotFound => None,
+            MergeStatus::Protected(status) => status.pull_request(),
+        }
+    }
+
+    /// Every merged PR pointing at the branch
+    pub fn pull_requests(&self) -> &[PullRequest] {
+        match self {
+            MergeStatus::Merged { pull_requests, .. }
+            | MergeStatus::StaleMerged { pull_requests, .. } => pull_requests,
+            MergeStatus::Diverged { .. }
+            | MergeStatus::MergedWithCommitsOnTop { .. }
+            | MergeStatus::NotFound => &[],
+            MergeStatus::Protected(status) => status.pull_requests(),
+        }
+    }
+
+    /// The distinct branches this branch was merged into
+    pub fn bases(&self) -> Vec<&str> {
+        let mut bases: Vec<&str> = vec![];
+        for pull_request in self.pull_requests() {
+            if !pull_request.base.is_empty() && !bases.contains(&pull_request.base.as_str()) {
+                bases.push(&pull_request.base);
+            }
+        }
+        bases
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MergeStatus::Merged { .. } => "merged",
+            MergeStatus::StaleMerged { .. } => "stale-merged",
+            MergeStatus::Diverged { .. } => "diverged",
+            MergeStatus::MergedWithCommitsOnTop { .. } => "merged-with-commits-on-top",
+            MergeStatus::NotFound => "not-found",
+            MergeStatus::Protected(_) => "protected",
+        }
+    }
+}
+
+pub fn classify<A: Fn(&str, &str) -> bool>(
+    branch: &Branch,
+    cache: &std::collections::HashMap<String, Vec<Branch>>,
+    by_name: &std::collections::HashMap<String, Vec<Branch>>,
+    options: &Options,
+    now: u64,
+    is_ancestor: A,
+) -> MergeStatus {
+    let remotes = cache_matches(branch, cache.get(&branch.commit_hash));
+    let (via, pull_requests) = if remotes.is_empty() {
+        let remote = match cache_matches(branch, by_name.get(&branch.name))
+            .first()
+            .copied()
+        {
+            Some(remote) => remote,
+            None => return MergeStatus::NotFound,
+        };
+        // The PR got more commits after this branch was last updated
+        if !is_ancestor(&branch.commit_hash, &remote.commit_hash) {
+            return match remote.pull_request.clone() {
+                Some(pull_request) => MergeStatus::Diverged { pull_request },
+                None => MergeStatus::NotFound,
+            };
+        }
+        (Via::Ancestor, remote.pull_request.iter().cloned().collect())
+    } else {
+        let pull_requests: Vec<PullRequest> = remotes
+            .iter()
+            .filter_map(|remote| remote.pull_request.clone())
+            .collect();
+        if pull_requests.is_empty() {
+            (Via::Ancestor, pull_requests)
+        } else {
+            (Via::ExactHash, pull_requests)
+        }
+    };
+    let age_days = branch
+        .committed_at
+        .map(|committed_at| now.saturating_sub(committed_at) / SECONDS_PER_DAY);
+    match (options.max_age_warn, age_days) {
+        (Some(max_age), Some(age_days)) if age_days > max_age => MergeStatus::StaleMerged {
+            age_days,
+            via,
+            pull_requests,
+        },
+        _ => MergeStatus::Merged { via, pull_requests },
+    }
+}
+
+/// Whether a cache entry can say anything about `branch`. A base branch is
+/// where PRs get merged into, and it usually points at the latest merge commit,
+/// as does every branch that was just created from it. So merge commits only
+/// count for the branch the PR came from.
+fn is_candidate(remote: &Branch, branch: &Branch) -> bool {
+    if remote.merge_commit && remote.name != branch.name {
+        return false;
+    }
+    remote
+        .pull_request
+        .as_ref()
+        .is_none_or(|pull_request| pull_request.base != branch.name)
+}
+
+fn cache_matches<'a>(branch: &Branch, remotes: Option<&'a Vec<Branch>>) -> Vec<&'a Branch> {
+    remotes
+        .into_iter()
+        .flatten()
+        .filter(|remote| is_candidate(remote, branch))
+        .collect()
+}
+
+/// The branch's last `depth` commits, newest first
+fn get_history(branch: &Branch, depth: usize) -> std::io::Result<Vec<String>> {
+    let output = new_command("git")
+        .args(vec![
+            "rev-list",
+            "-n",
+            &depth.to_string(),
+            &branch.commit_hash,
+        ])
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// Looks for the newest commit in `history` that a merged PR points at
+fn match_history(
+    branch: &Branch,
+    history: &[String],
+    cache: &std::collections::HashMap<String, Vec<Branch>>,
+) -> Option<MergeStatus> {
+    history
+        .iter()
+        .enumerate()
+        .find_map(|(commits_on_top, commit_hash)| {
+            let remotes = cache_matches(branch, cache.get(commit_hash));
+            if remotes.is_empty() {
+                return None;
+            }
+            Some(MergeStatus::MergedWithCommitsOnTop {
+                commits_on_top,
+                pull_request: remotes
+                    .iter()
+                    .find_map(|remote| remote.pull_request.clone()),
+            })
+        })
+}
+
+/// `--keep-latest`: protects the `keep` newest merged branches starting with
+/// `prefix`, and returns their names. Unmerged branches aren't ranked, so they
+/// can't push a merged one out. Branches without a commit date count as new,
+/// since there's no telling how old they are
+fn keep_latest(evaluated: &mut [(Branch, MergeStatus)], prefix: &str, keep: usize) -> Vec<String> {
+    let mut family: Vec<(Option<u64>, usize)> = evaluated
+        .iter()
+        .enumerate()
+        .filter(|(_, (branch, status))| branch.name.starts_with(prefix) && status.via().is_some())
+        .map(|(index, (branch, _))| (branch.committed_at, index))
+        .collect();
+    family.sort_by_key(|(committed_at, _)| std::cmp::Reverse(committed_at.unwrap_or(u64::MAX)));
+    let mut kept = vec![];
+    for (_, index) in family.into_iter().take(keep) {
+        let (branch, status) = &mut evaluated[index];
+        let merged = std::mem::replace(status, MergeStatus::NotFound);
+        *status = MergeStatus::Protected(Box::new(merged));
+        kept.push(branch.name.to_string());
+    }
+    kept
+}
+
+/// Why a branch wasn't listed or deleted, in the order they're reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SkipReason {
+    CheckedOut,
+    Protected,
+    KeptLatest,
+    NeverMerged,
+    Unlabeled,
+    Ahead,
+    Declined,
+    Failed,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SkipReason::CheckedOut => write!(f, "checked out in a work tree"),
+            SkipReason::Protected => write!(f, "protected by --protect or the keep file"),
+            SkipReason::KeptLatest => write!(f, "one of the --keep-latest branches"),
+            SkipReason::NeverMerged => write!(f, "on the --never-merged-commit list"),
+            SkipReason::Unlabeled => write!(f, "PR doesn't have a --delete-if-label label"),
+            SkipReason::Ahead => write!(f, "has commits its remote doesn't"),
+            SkipReason::Declined => write!(f, "deletion wasn't confirmed"),
+            SkipReason::Failed => write!(f, "couldn't be deleted"),
+        }
+    }
+}
+
+/// Why `evaluate` kept a branch from being listed, if it was merged otherwise.
+/// Unmerged branches aren't skipped, whatever protects them
+fn skip_reason(
+    status: &MergeStatus,
+    overridden: Option<&MergeStatus>,
+    is_checked_out: bool,
+) -> Option<SkipReason> {
+    match status {
+        MergeStatus::Protected(inner) if inner.via().is_some() && is_checked_out => {
+            Some(SkipReason::CheckedOut)
+        }
+        MergeStatus::Protected(inner) if inner.via().is_some() => Some(SkipReason::Protected),
+        _ if overridden.is_some_and(|status| status.via().is_some()) => {
+            Some(SkipReason::NeverMerged)
+        }
+        _ => None,
+    }
+}
+
+/// The verbose summary of skipped branches, grouped by why they were skipped
+fn skipped_summary(mut skipped: Vec<(SkipReason, String)>, total: usize) -> Vec<String> {
+    if skipped.is_empty() {
+        return vec![];
+    }
+    skipped.sort();
+    let mut lines = vec![format!("Skipped {} of {} branches:", skipped.len(), total)];
+    let mut reasons = skipped
+        .iter()
+        .map(|(reason, _)| *reason)
+        .collect::<Vec<_>>();
+    reasons.dedup();
+    for reason in reasons {
+        let names: Vec<&str> = skipped
+            .iter()
+            .filter(|(skip_reason, _)| *skip_reason == reason)
+            .map(|(_, name)| name.as_str())
+            .collect();
+        lines.push(format!("  {}: {}", reason, names.join(", ")));
+    }
+    lines
+}
+
+fn log_skipped(skipped: Vec<(SkipReason, String)>, total: usize) {
+    for line in skipped_summary(skipped, total) {
+        info!("{}", line);
+    }
+}
+
+/// `--stats`: what a run cost, to compare detection modes on large repositories
+#[derive(Debug, Default)]
+struct Stats {
+    subprocesses: usize,
+    lines_parsed: usize,
+    /// Commits merged PRs (or `--merged-into`) point at
+    cached_commits: usize,
+    /// Branch names with a merged PR
+    cached_names: usize,
+    /// Branch names with an open PR
+    open_names: usize,
+    local_branches: Duration,
+    remote_branches: Duration,
+    classifying: Duration,
+    deleting: Duration,
+    total: Duration,
+}
+
+fn duration_ms(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+impl Stats {
+    /// Fills in the counters, which are global
+    fn collect(self) -> Stats {
+        Stats {
+            subprocesses: SUBPROCESSES.load(Ordering::Relaxed),
+            lines_parsed: LINES_PARSED.load(Ordering::Relaxed),
+            ..self
+        }
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"subprocesses\":{},\"lines_parsed\":{},\"cache\":{{\"commits\":{},\"names\":{},\"open_names\":{}}},\"timings_ms\":{{\"local_branches\":{},\"remote_branches\":{},\"classifying\":{},\"deleting\":{},\"total\":{}}}}}",
+            self.subprocesses,
+            self.lines_parsed,
+            self.cached_commits,
+            self.cached_names,
+            self.open_names,
+            duration_ms(self.local_branches),
+            duration_ms(self.remote_branches),
+            duration_ms(self.classifying),
+            duration_ms(self.deleting),
+            duration_ms(self.total)
+        )
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "subprocesses: {}", self.subprocesses)?;
+        writeln!(f, "lines parsed: {}", self.lines_parsed)?;
+        writeln!(
+            f,
+            "cache: {} commits, {} branch names, {} with open PRs",
+            self.cached_commits, self.cached_names, self.open_names
+        )?;
+        writeln!(f, "local branches: {:?}", self.local_branches)?;
+        writeln!(f, "remote branches: {:?}", self.remote_branches)?;
+        writeln!(f, "classifying: {:?}", self.classifying)?;
+        writeln!(f, "deleting: {:?}", self.deleting)?;
+        writeln!(f, "total: {:?}", self.total)
+    }
+}
+
+fn print_stats(stats: Stats, format: Format) {
+    let stats = stats.collect();
+    match format {
+        Format::Json => eprintln!("{}", stats.to_json()),
+        _ => eprint!("{}", stats),
+    }
+}
+
+/// Stable, and branches without what's sorted by (a commit date or a PR) stay
+/// last even when `reverse`d
+fn sort_evaluated(evaluated: &mut [(Branch, MergeStatus)], sort_by: SortBy, reverse: bool) {
+    use std::cmp::Ordering;
+    fn key(branch: &Branch, status: &MergeStatus, sort_by: SortBy) -> Option<u64> {
+        match sort_by {
+            SortBy::Name => Some(0),
+            SortBy::Age => branch.committed_at,
+            SortBy::Pr => status
+                .pull_request()
+                .and_then(|pr| pr.number.trim_start_matches('#').parse().ok()),
+        }
+    }
+    evaluated.sort_by(|(a, a_status), (b, b_status)| {
+        let ordering = match (key(a, a_status, sort_by), key(b, b_status, sort_by)) {
+            (Some(a_key), Some(b_key)) => a_key.cmp(&b_key).then_with(|| a.name.cmp(&b.name)),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => a.name.cmp(&b.name),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// What `evaluate` decided about a branch
+#[derive(Debug, PartialEq)]
+pub struct Evaluation {
+    /// What's listed and acted on
+    pub status: MergeStatus,
+    /// What the heuristics said, when `--never-merged-commit` overrode it
+    pub overridden: Option<MergeStatus>,
+}
+
+/// Classifies `branch` with every heuristic that's enabled, producing the status
+/// that's listed and acted on. `is_ancestor` is passed on to `classify`
+pub fn evaluate<A: Fn(&str, &str) -> bool>(
+    branch: &Branch,
+    cache: &std::collections::HashMap<String, Vec<Branch>>,
+    by_name: &std::collections::HashMap<String, Vec<Branch>>,
+    protected: &[String],
+    options: &Options,
+    now: u64,
+    is_ancestor: A,
+) -> std::io::Result<Evaluation> {
+    let status = classify(branch, cache, by_name, options, now, is_ancestor);
+    let status = match (status, options.match_history) {
+        // Branches read from a file might not exist in this repository
+        (status @ (MergeStatus::NotFound | MergeStatus::Diverged { .. }), Some(depth))
+            if options.local_input.is_none() =>
+        {
+            let history = get_history(branch, depth)?;
+            match_history(branch, &history, cache).unwrap_or(status)
+        }
+        (status, _) => status,
+    };
+    // Overrides every heuristic, so these commits can never get deleted
+    let (status, overridden) =
+        if is_never_merged(&branch.commit_hash, &options.never_merged_commits) {
+            info!(
+                "{} points at {}, which is never treated as merged",
+                branch.name, branch.commit_hash
+            );
+            (MergeStatus::NotFound, Some(status))
+        } else {
+            (status, None)
+        };
+    let status = if protected.iter().any(|glob| glob_match(glob, &branch.name)) {
+        MergeStatus::Protected(Box::new(status))
+    } else {
+        status
+    };
+    Ok(Evaluation { status, overridden })
+}
+
+/// What every heuristic says about a single branch, for `--explain`
+struct Explanation {
+    branch: Branch,
+    found_in_cache: bool,
+    /// PRs whose head or merge commit is the branch's tip
+    pull_requests: Vec<PullRequest>,
+    /// PRs from a branch with the same name that aren't merged yet
+    open_pull_requests: Vec<PullRequest>,
+    /// `None` when no merged PR has the branch's name
+    name_ancestor: Option<bool>,
+    /// `None` when `--match-history` is off
+    history: Option<Option<usize>>,
+    protected: bool,
+    decision: MergeStatus,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn explain<A: Fn(&str, &str) -> bool>(
+    branch: &Branch,
+    cache: &std::collections::HashMap<String, Vec<Branch>>,
+    by_name: &std::collections::HashMap<String, Vec<Branch>>,
+    open_pull_requests: &std::collections::HashMap<String, Vec<PullRequest>>,
+    protected: &[String],
+    options: &Options,
+    now: u64,
+    is_ancestor: A,
+) -> std::io::Result<Explanation> {
+    let remotes = cache_matches(branch, cache.get(&branch.commit_hash));
+    let pull_requests = remotes
+        .iter()
+        .filter_map(|remote| remote.pull_request.clone())
+        .collect();
+    let name_ancestor = cache_matches(branch, by_name.get(&branch.name))
+        .first()
+        .map(|remote| is_ancestor(&branch.commit_hash, &remote.commit_hash));
+    let history =
+        match options.match_history {
+            Some(depth) if options.local_input.is_none() => {
+                let history = get_history(branch, depth)?;
+                Some(history.iter().position(|commit_hash| {
+                    !cache_matches(branch, cache.get(commit_hash)).is_empty()
+                }))
+            }
+            _ => None,
+        };
+    Ok(Explanation {
+        branch: branch.clone(),
+        found_in_cache: !remotes.is_empty(),
+        pull_requests,
+        open_pull_requests: open_pull_requests
+            .get(&branch.name)
+            .cloned()
+            .unwrap_or_default(),
+        name_ancestor,
+        history,
+        protected: protected.iter().any(|glob| glob_match(glob, &branch.name)),
+        decision: evaluate(
+            branch,
+            cache,
+            by_name,
+            protected,
+            options,
+            now,
+            &is_ancestor,
+        )?
+        .status,
+    })
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+fn pull_request_json(pr: &PullRequest) -> String {
+    let labels: Vec<String> = pr.labels.iter().map(|label| json_string(label)).collect();
+    format!(
+        "{{\"number\":{},\"title\":{},\"base\":{},\"labels\":[{}]}}",
+        json_string(&pr.number),
+        json_string(&pr.title),
+        json_string(&pr.base),
+        labels.join(",")
+    )
+}
+
+impl Explanation {
+    fn to_json(&self) -> String {
+        let pull_requests: Vec<String> = self.pull_requests.iter().map(pull_request_json).collect();
+        let open_pull_requests: Vec<String> = self
+            .open_pull_requests
+            .iter()
+            .map(pull_request_json)
+            .collect();
+        let history = match self.history {
+            Some(commits_on_top) => {
+                format!("{{\"commits_on_top\":{}}}", json_option(commits_on_top))
+            }
+            None => "null".to_string(),
+        };
+        let divergence = match self.branch.divergence {
+            Some(divergence) => format!(
+                "{{\"ahead\":{},\"behind\":{}}}",
+                divergence.ahead, divergence.behind
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"branch\":{},\"commit\":{},\"divergence\":{},\"found_in_cache\":{},\"pull_requests\":[{}],\"open_pull_requests\":[{}],\"heuristics\":{{\"exact_hash\":{},\"name_ancestor\":{},\"history\":{}}},\"protected\":{},\"decision\":{}}}",
+            json_string(&self.branch.name),
+            json_string(&self.branch.commit_hash),
+            divergence,
+            self.found_in_cache,
+            pull_requests.join(","),
+            open_pull_requests.join(","),
+            !self.pull_requests.is_empty(),
+            json_option(self.name_ancestor),
+            history,
+            self.protected,
+            json_string(self.decision.label())
+        )
+    }
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "branch: {}", self.branch.name)?;
+        writeln!(f, "commit: {}", self.branch.commit_hash)?;
+        if let Some(divergence) = self.branch.divergence {
+            writeln!(
+                f,
+                "divergence: {} ahead, {} behind",
+                divergence.ahead, divergence.behind
+            )?;
+        }
+        writeln!(f, "found in cache: {}", self.found_in_cache)?;
+        for pr in &self.pull_requests {
+            writeln!(f, "pull request: {} {}", pr.number, pr.title)?;
+        }
+        for pr in &self.open_pull_requests {
+            writeln!(f, "open pull request: {} {}", pr.number, pr.title)?;
+        }
+        writeln!(f, "exact hash: {}", !self.pull_requests.is_empty())?;
+        match self.name_ancestor {
+            Some(is_ancestor) => writeln!(f, "name ancestor: {}", is_ancestor)?,
+            None => writeln!(f, "name ancestor: no PR with this name")?,
+        }
+        match self.history {
+            Some(Some(commits_on_top)) => {
+                writeln!(f, "history: {} commit(s) on top", commits_on_top)?
+            }
+            Some(None) => writeln!(f, "history: no match")?,
+            None => writeln!(f, "history: disabled")?,
+        }
+        writeln!(f, "protected: {}", self.protected)?;
+        writeln!(f, "decision: {}", self.decision.label())
+    }
+}
+
+const MAX_TITLE_LENGTH: usize = 50;
+
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_length - 1).collect();
+    format!("{}…", truncated)
+}
+
+fn confirmation_prompt(branch: &Branch, pull_request: Option<&PullRequest>) -> String {
+    match pull_request {
+        Some(pr) if pr.title.is_empty() => {
+            format!("Delete {}? (merged in {}) [y/N] ", branch.name, pr.number)
+        }
+        Some(pr) => format!(
+            "Delete {}? (merged in {} \"{}\") [y/N] ",
+            branch.name,
+            pr.number,
+            truncate(&pr.title, MAX_TITLE_LENGTH)
+        ),
+        None => format!("Delete {}? [y/N] ", branch.name),
+    }
+}
+
+fn confirm(prompt: &str) -> bool {
+    eprint!("{}", prompt);
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Counts the commits on `branch` that aren't on `upstream`.
+/// `None` when `upstream` doesn't exist, e.g. when it was deleted after merging.
+fn commits_ahead_of(upstream: &str, branch: &str) -> std::io::Result<Option<usize>> {
+    let output = new_command("git")
+        .args(vec![
+            "rev-list",
+            "--count",
+            &format!("{}..{}", upstream, branch),
+        ])
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+}
+
+/// `git merge-base --is-ancestor`, the check `classify` gets for same-name PRs.
+/// Fails when git can't resolve one of the commits.
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> std::io::Result<bool> {
+    let status = new_command("git")
+        .args(vec!["merge-base", "--is-ancestor", ancestor, descendant])
+        .stderr(Stdio::null())
+        .status()?;
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(Error::other(format!(
+            "Can't tell whether {} is an ancestor of {}",
+            ancestor, descendant
+        ))),
+    }
+}
+
+/// Fills `{name}`, `{sha}`, `{date}` and `{author}` in a `--delete-command`,
+/// quoted for the shell. In one pass, so a branch named `{sha}` stays a name.
+/// `{date}` and `{author}` are empty for branches read without them.
+fn render_delete_command(template: &str, branch: &Branch) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = [
+            ("{name}", Some(branch.name.as_str())),
+            ("{sha}", Some(branch.commit_hash.as_str())),
+            ("{date}", branch.committed_date.as_deref()),
+            ("{author}", branch.author.as_deref()),
+        ]
+        .iter()
+        .find(|(placeholder, _)| rest.starts_with(placeholder))
+        .map(|(placeholder, value)| (placeholder.len(), value.unwrap_or("")));
+        match value {
+            Some((length, value)) => {
+                rendered.push_str(&shell_quote(value));
+                rest = &rest[length..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Returns whether the branch was deleted
+fn delete_branch(branch: &Branch, delete_command: Option<&str>) -> std::io::Result<bool> {
+    let status = match delete_command {
+        Some(template) => {
+            let command = render_delete_command(template, branch);
+            info!("> Running {}", command);
+            shell(&command).status()?
+        }
+        None => new_command("git")
+            .args(vec!["branch", "-D", &branch.name])
+            .status()?,
+    };
+    if !status.success() {
+        warn!("Couldn't delete {}", branch.name);
+    }
+    Ok(status.success())
+}
+
+/// The remote `branch` tracks, from `branch.<name>.remote`
+fn upstream_remote(branch: &Branch) -> Option<String> {
+    let output = new_command("git")
+        .args(vec![
+            "config",
+            "--get",
+            &format!("branch.{}.remote", branch.name),
+        ])
+        .output()
+        .ok()?;
+    let remote = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // `.` means the upstream is a local branch, there's nothing remote to delete
+    Some(remote).filter(|remote| !remote.is_empty() && remote != ".")
+}
+
+fn has_remote_branch(remote: &str, branch: &Branch) -> std::io::Result<bool> {
+    let tracking_ref = format!("refs/remotes/{}/{}", remote, branch.name);
+    let status = new_command("git")
+        .args(vec!["rev-parse", "--verify", "--quiet", &tracking_ref])
+        .stdout(Stdio::null())
+        .status()?;
+    Ok(status.success())
+}
+
+fn remote_confirmation_prompt(remote: &str, branch: &Branch) -> String {
+    format!(
+        "Delete {} on the remote `{}`? This affects everyone using it [y/N] ",
+        branch.name.bold(),
+        remote
+    )
+}
+
+fn delete_remote_branch(remote: &str, branch: &Branch) -> std::io::Result<()> {
+    let status = new_command("git")
+        .args(vec!["push", remote, "--delete", &branch.name])
+        .status()?;
+    if !status.success() {
+        warn!("Couldn't delete {} on {}", branch.name, remote);
+    }
+    Ok(())
+}
+
+/// Quotes `value` for `sh` unless it's made of characters that are safe unquoted
+fn shell_quote(value: &str) -> String {
+    let safe = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./@+=,%".contains(c));
+    if safe && !value.is_empty() {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// The command that recreates `branch` where it pointed before it got deleted
+fn restore_command(branch: &Branch) -> String {
+    format!(
+        "git branch {} {}",
+        shell_quote(&branch.name),
+        branch.commit_hash
+    )
+}
+
+fn create_restore_script(path: &str) -> std::io::Result<std::fs::File> {
+    let mut file = std::fs::File::create(path)
+        .map_err(|err| Error::new(err.kind(), format!("Can't create {}: {}", path, err)))?;
+    writeln!(file, "#!/bin/sh")?;
+    writeln!(file, "# Recreates the branches deleted by merged_branches")?;
+    Ok(file)
+}
+
+/// Runs `git remote prune` and returns the tracking refs it removed
+fn prune_tracking_refs(remote: &str) -> std::io::Result<Vec<String>> {
+    let output = new_command("git")
+        .args(vec!["remote", "prune", remote])
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(parse_pruned_refs(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses lines like ` * [pruned] origin/feature/foo`
+fn parse_pruned_refs(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("* [pruned] "))
+        .map(|tracking_ref| tracking_ref.to_string())
+        .collect()
+}
+
+/// The names are quoted, since the `git branch -D` line is meant to be pasted
+fn next_step_hint(branch_names: &[String]) -> Option<String> {
+    if branch_names.is_empty() {
+        return None;
+    }
+    let quoted: Vec<String> = branch_names.iter().map(|name| shell_quote(name)).collect();
+    Some(format!(
+        "# delete them: merged_branches --delete\n# or: git branch -D {}",
+        quoted.join(" ")
+    ))
+}
+
+/// `--progress-every`: whether to log progress before classifying the
+/// branch at `index`, so never before the first one
+fn is_progress_due(index: usize, progress_every: usize) -> bool {
+    progress_every != 0 && index > 0 && index.is_multiple_of(progress_every)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+struct Check {
+    description: String,
+    passed: bool,
+    /// Failing a critical check makes `doctor` exit with 1
+    critical: bool,
+}
+
+impl Check {
+    fn new<T: Into<String>>(description: T, passed: bool, critical: bool) -> Check {
+        Check {
+            description: description.into(),
+            passed,
+            critical,
+        }
+    }
+
+    fn render(&self) -> String {
+        match (self.passed, self.critical) {
+            (true, _) => format!("{} {}", "✓".green(), self.description),
+            (false, true) => format!("{} {}", "✗".red(), self.description),
+            (false, false) => format!("{} {}", "!".yellow(), self.description),
+        }
+    }
+}
+
+/// The checks take a `git` closure that runs git with some arguments, so
+/// they can be tested without a repository
+fn check_git<G: Fn(&[&str]) -> std::io::Result<Output>>(git: G) -> Check {
+    match git(&["rev-parse", "--is-bare-repository"]) {
+        Ok(ref output) if output.status.success() && output.stdout.starts_with(b"true") => {
+            Check::new(
+                "git is installed and this is a bare git repository",
+                true,
+                true,
+            )
+        }
+        Ok(ref output) if output.status.success() => {
+            Check::new("git is installed and this is a git repository", true, true)
+        }
+        Ok(_) => Check::new(
+            "git is installed, but this isn't a git repository",
+            false,
+            true,
+        ),
+        Err(_) => Check::new("git isn't installed", false, true),
+    }
+}
+
+fn check_remote<G, F>(remote: &str, git: G, host_mapping: F) -> Check
+where
+    G: Fn(&[&str]) -> std::io::Result<Output>,
+    F: Fn(&str) -> Option<Provider>,
+{
+    let url = match git(&["remote", "get-url", remote]) {
+        Ok(ref output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => return Check::new(format!("There's no `{}` remote", remote), false, false),
+    };
+    match provider_for_url(&url, host_mapping) {
+        Some(provider) => Check::new(format!("{} uses {}", url, provider), true, false),
+        None => Check::new(
+            format!(
+                "{} isn't a known host, set `git config merged-branches.<host>.provider`",
+                url
+            ),
+            false,
+            false,
+        ),
+    }
+}
+
+/// `run` runs the provider's CLI with the arguments that check its login
+fn check_provider<R: FnOnce(&[&str]) -> std::io::Result<Output>>(
+    provider: &Provider,
+    run: R,
+) -> Check {
+    let auth_check = match provider {
+        Provider::Command(_) => {
+            return Check::new(format!("Using `{}` to get PRs", provider), true, true)
+        }
+        Provider::Hub => vec!["api", "user"],
+        Provider::Gh => vec!["auth", "status"],
+        Provider::GitLab | Provider::Bitbucket => {
+            return Check::new(
+                format!(
+                    "{} isn't supported yet, `git branch --merged` will be used instead",
+                    provider
+                ),
+                false,
+                false,
+            )
+        }
+    };
+    match run(&auth_check) {
+        Ok(ref output) if output.status.success() => Check::new(
+            format!("`{}` is installed and authenticated", provider),
+            true,
+            true,
+        ),
+        Ok(_) => Check::new(
+            format!("`{}` is installed, but isn't authenticated", provider),
+            false,
+            true,
+        ),
+        Err(_) => Check::new(format!("`{}` isn't installed", provider), false, true),
+    }
+}
+
+fn check_default_branch<G: Fn(&[&str]) -> std::io::Result<Output>>(
+    default_branch: &str,
+    git: G,
+) -> Check {
+    match git(&["rev-parse", "--verify", "--quiet", default_branch]) {
+        Ok(ref output) if output.status.success() => Check::new(
+            format!("The default branch resolves to {}", default_branch),
+            true,
+            false,
+        ),
+        _ => Check::new(
+            "Can't resolve the default branch, try `git remote set-head origin --auto`",
+            false,
+            false,
+        ),
+    }
+}
+
+/// Prints a pass/fail report of the environment and exits with 1 when
+/// a critical check fails
+fn doctor(options: &Options) -> ! {
+    let git = |args: &[&str]| new_command("git").args(args).output();
+    let git_check = check_git(git);
+    let mut checks = vec![];
+    if git_check.passed {
+        let provider = options
+            .provider
+            .clone()
+            .unwrap_or_else(|| detect_provider(&options.remote));
+        checks.push(git_check);
+        checks.push(check_remote(&options.remote, git, configured_provider));
+        checks.push(check_provider(&provider, |args| {
+            let mut command = new_command(provider.to_string());
+            command.args(args);
+            forward_token(&mut command, &provider, options.token_env.as_deref());
+            command.output()
+        }));
+        checks.push(check_default_branch(&get_default_branch(), git));
+    } else {
+        checks.push(git_check);
+    }
+    for check in &checks {
+        println!("{}", check.render());
+    }
+    let failed = checks.iter().any(|check| check.critical && !check.passed);
+    exit(if failed { 1 } else { 0 });
+}
+
+/// Everything `merged_branches` does, from parsing the arguments to the exit code
+pub fn run() -> std::io::Result<()> {
+    let run_started = Instant::now();
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message.red());
+            exit(2);
+        }
+    };
+    init_logger(options.verbosity, options.quiet);
+    ctrlc::set_handler(on_interrupt).expect("Can't install the Ctrl-C handler");
+    if options.doctor {
+        doctor(&options);
+    }
+    if let Some(path) = &options.never_merged_file {
+        let commits = read_never_merged_file(path)?;
+        options.never_merged_commits.extend(commits);
+    }
+
+    let (tx_local, rx_local) = std::sync::mpsc::channel();
+    let (tx_remote, rx_remote) = std::sync::mpsc::channel();
+
+    let local_input = options.local_input.clone();
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        let source = if local_input.is_some() { "file" } else { "git" };
+        let branches_vec: Vec<Branch> = match local_input {
+            Some(path) => {
+                info!("> Reading local branches from {}...", path);
+                read_local_branches(&path)
+                    .expect("Can't read local input")
+                    .collect()
+            }
+            None => {
+                info!("> Collecting local branches from git...");
+                get_local_branches()
+                    .expect("Can't get local branches")
+                    .collect()
+            }
+        };
+        // Logged before sending, so the main thread can't exit first
+        let done = format!("> Done collecting local branches from {}!", source);
+        info!("{}", done.green());
+        tx_local
+            .send((branches_vec, started.elapsed()))
+            .expect("Can't send local branches");
+    });
+
+    // The adaptive limit depends on the local branches, so it can't
+    // run concurrently with collecting them. Provider commands don't get
+    // the limit, so there's nothing to grow
+    let is_command = matches!(options.provider, Some(Provider::Command(_)));
+    if options.remote_limit_auto && is_command {
+        info!("> --provider-command doesn't take a limit, so --remote-limit-auto is ignored");
+    }
+    let auto_limit = options.remote_limit_auto && options.pr_input.is_none() && !is_command;
+    if !auto_limit && !options.no_remote {
+        let limit = options.limit;
+        let pr_input = options.pr_input.clone();
+        let provider = options.provider.clone();
+        let remote = options.remote.clone();
+        let merged_states = options.merged_states.clone();
+        let token_env = options.token_env.clone();
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let source = if pr_input.is_some() { "file" } else { "GitHub" };
+            let remote_cache = match pr_input {
+                Some(path) => {
+                    info!("> Reading remote branches from {}...", path);
+                    let pull_requests = read_pull_requests(&path).expect("Can't read PR input");
+                    // Files use the `hub` vocabulary unless told otherwise
+                    let merged_states =
+                        merged_states.unwrap_or_else(|| Provider::Hub.merged_states());
+                    let mut cache = RemoteCache::default();
+                    cache.add_pull_requests(pull_requests, &merged_states);
+                    cache
+                }
+                None => {
+                    info!("> Collecting remote branches from GitHub...");
+                    let provider = provider.unwrap_or_else(|| detect_provider(&remote));
+                    let merged_states = merged_states.unwrap_or_else(|| provider.merged_states());
+                    or_merged_into_default(
+                        &provider,
+                        get_remote_branches(&provider, limit, &merged_states, token_env.as_deref()),
+                    )
+                }
+            };
+            let done = format!("> Done collecting remote branches from {}!", source);
+            info!("{}", done.green());
+            tx_remote
+                .send((remote_cache, started.elapsed()))
+                .expect("Can't send local branches");
+        });
+    }
+
+    let (mut local_branches, local_elapsed) = rx_local.recv().expect("Can't get local branches");
+    // Branches are classified independently, even when several share a tip
+    // commit. Sorting keeps the output and the deletion order reproducible.
+    local_branches.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(scope) = &options.scope {
+        local_branches.retain(|branch| glob_match(scope, &branch.name));
+    }
+    // Everything else gets merged into it, so it's never a candidate itself
+    let default_branch = if options.local_input.is_none() {
+        let default_branch = local_default_branch();
+        local_branches.retain(|branch| branch.name != default_branch);
+        Some(default_branch)
+    } else {
+        None
+    };
+    if let Some(reference) = &options.merge_base_with {
+        add_divergences(reference, &mut local_branches);
+    }
+    let mut protected = options.protect.clone();
+    protected.extend(read_keep_file(&options)?);
+    // Checked out branches can't be deleted
+    let checked_out = if options.local_input.is_none() {
+        checked_out_branches()
+    } else {
+        vec![]
+    };
+    protected.extend(checked_out.iter().cloned());
+    let (mut remote_cache, remote_elapsed) = if options.no_remote {
+        (RemoteCache::default(), Duration::default())
+    } else if auto_limit {
+        let started = Instant::now();
+        info!("> Collecting remote branches from GitHub...");
+        let limit = if options.limit == 0 {
+            DEFAULT_LIMIT
+        } else {
+            options.limit
+        };
+        let provider = options
+            .provider
+            .clone()
+            .unwrap_or_else(|| detect_provider(&options.remote));
+        let merged_states = options
+            .merged_states
+            .clone()
+            .unwrap_or_else(|| provider.merged_states());
+        let targets: Vec<&Branch> = local_branches
+            .iter()
+            .filter(|branch| !protected.iter().any(|glob| glob_match(glob, &branch.name)))
+            .collect();
+        let remote_cache = or_merged_into_default(
+            &provider,
+            get_remote_branches_auto(
+                &provider,
+                &targets,
+                limit,
+                &merged_states,
+                options.token_env.as_deref(),
+            ),
+        );
+        info!(
+            "{}",
+            "> Done collecting remote branches from GitHub!".green()
+        );
+        (remote_cache, started.elapsed())
+    } else {
+        rx_remote.recv().expect("Can't get local branches")
+    };
+    for base in &options.merged_into {
+        remote_cache.extend(merged_into(base, &options.remote)?);
+    }
+    let RemoteCache {
+        by_hash: cache,
+        by_name,
+        open: open_pull_requests,
+    } = remote_cache;
+    let mut stats = Stats {
+        cached_commits: cache.len(),
+        cached_names: by_name.len(),
+        open_names: open_pull_requests.len(),
+        local_branches: local_elapsed,
+        remote_branches: remote_elapsed,
+        ..Stats::default()
+    };
+    let now = unix_now();
+    let highlight = stdout().is_terminal();
+    // Branches read from a file might not exist in this repository
+    let check_ancestry = |ancestor: &str, descendant: &str| {
+        options.local_input.is_none() && is_ancestor(ancestor, descendant).unwrap_or(false)
+    };
+
+    if let Some(name) = &options.explain {
+        let branch = match local_branches.iter().find(|branch| &branch.name == name) {
+            Some(branch) => branch,
+            None if default_branch.as_ref() == Some(name) => {
+                error!(
+                    "{} is the default branch, which is never listed or deleted",
+                    name
+                );
+                exit(1);
+            }
+            None => {
+                error!("There's no local branch named {}", name);
+                exit(1);
+            }
+        };
+        let explanation = explain(
+            branch,
+            &cache,
+            &by_name,
+            &open_pull_requests,
+            &protected,
+            &options,
+            now,
+            check_ancestry,
+        )?;
+        match options.format {
+            Format::Json => println!("{}", explanation.to_json()),
+            _ => print!("{}", explanation),
+        }
+        if options.stats {
+            stats.total = run_started.elapsed();
+            print_stats(stats, options.format);
+        }
+        return Ok(());
+    }
+
+    let abbrev = options.abbrev.unwrap_or_else(|| {
+        // Branches read from a file don't belong to this repository's config
+        let configured = if options.local_input.is_none() {
+            core_abbrev()
+        } else {
+            None
+        };
+        configured.unwrap_or(DEFAULT_ABBREV)
+    });
+    let mut evaluated = vec![];
+    let mut skipped = vec![];
+    let total = local_branches.len();
+    let mut detection_elapsed = Duration::default();
+
+    for (index, branch) in local_branches.into_iter().enumerate() {
+        if is_progress_due(index, options.progress_every) {
+            info!("> Processed {}/{} branches", index, total);
+        }
+        let started = Instant::now();
+        let Evaluation { status, overridden } = evaluate(
+            &branch,
+            &cache,
+            &by_name,
+            &protected,
+            &options,
+            now,
+            check_ancestry,
+        )?;
+        let elapsed = started.elapsed();
+        trace!("> Classified {} in {:?}", branch.name, elapsed);
+        detection_elapsed += elapsed;
+        match &status {
+            MergeStatus::NotFound => info!(
+                "Can't find {} ({})",
+                branch.name,
+                abbreviate(&branch.commit_hash, abbrev)
+            ),
+            MergeStatus::Diverged { pull_request } => warn!(
+                "{} matches merged PR {} but commit differs ({})",
+                branch.name,
+                pull_request.number,
+                abbreviate(&branch.commit_hash, abbrev)
+            ),
+            MergeStatus::Merged { .. } => {}
+            MergeStatus::StaleMerged { age_days, .. } => {
+                info!("{} is stale-merged ({} days old)", branch.name, age_days)
+            }
+            MergeStatus::MergedWithCommitsOnTop {
+                commits_on_top,
+                pull_request,
+            } => warn!(
+                "{} was merged{}, with {} commit(s) on top",
+                branch.name,
+                pull_request
+                    .as_ref()
+                    .map(|pr| format!(" in {}", pr.number))
+                    .unwrap_or_default(),
+                commits_on_top
+            ),
+            MergeStatus::Protected(_) => info!("{} is protected", branch.name),
+        };
+        let is_checked_out = checked_out.contains(&branch.name);
+        if let Some(reason) = skip_reason(&status, overridden.as_ref(), is_checked_out) {
+            skipped.push((reason, branch.name.to_string()));
+        }
+        if !status.bases().is_empty() {
+            info!(
+                "{} was merged into {}",
+                branch.name,
+                status.bases().join(", ")
+            );
+        }
+        evaluated.push((branch, status));
+    }
+    if let Some(keep) = options.keep_latest {
+        for prefix in &options.prefixes {
+            for name in keep_latest(&mut evaluated, prefix, keep) {
+                info!(
+                    "{} is one of the {} latest {}* branches",
+                    name, keep, prefix
+                );
+                skipped.push((SkipReason::KeptLatest, name));
+            }
+        }
+    }
+    sort_evaluated(&mut evaluated, options.sort_by, options.reverse);
+
+    let required_unmerged: Vec<String> = evaluated
+        .iter()
+        .filter(|(branch, status)| {
+            !status.is_merged()
+                && options
+                    .fail_on_unmerged
+                    .iter()
+                    .any(|glob| glob_match(glob, &branch.name))
+        })
+        .map(|(branch, _)| branch.name.to_string())
+        .collect();
+
+    let merged_count = evaluated
+        .iter()
+        .filter(|(_, status)| status.counts_as_merged(options.count_protected))
+        .count();
+
+    let mut to_delete = vec![];
+    if options.count_only {
+        println!("{}", merged_count);
+    } else if options.delete {
+        to_delete = evaluated
+            .into_iter()
+            .filter(|(_, status)| status.via().is_some())
+            .collect();
+        if !options.delete_if_label.is_empty() {
+            let (labeled, unlabeled): (Vec<_>, Vec<_>) =
+                to_delete.into_iter().partition(|(_, status)| {
+                    status.pull_requests().iter().any(|pr| {
+                        options
+                            .delete_if_label
+                            .iter()
+                            .any(|label| pr.has_label(label))
+                    })
+                });
+            // Only reported, like without --delete
+            for (branch, _) in &unlabeled {
+                println!("{}", branch.name);
+                skipped.push((SkipReason::Unlabeled, branch.name.to_string()));
+            }
+            to_delete = labeled;
+        }
+    } else {
+        match options.format {
+            Format::Text if options.oneline => print_oneline(&evaluated, abbrev, highlight)?,
+            Format::Text => {
+                let open_pull_requests =
+                    Some(&open_pull_requests).filter(|_| options.annotate_open_pr);
+                print_text(evaluated, &options, open_pull_requests, highlight)?
+            }
+            Format::Csv => print_csv(&evaluated, options.merge_base_with.is_some()),
+            Format::GitHubActions => print_github_actions(&evaluated, merged_count),
+            Format::Json => print_json(&evaluated),
+        }
+    }
+
+    let deletion_started = Instant::now();
+    let mut restore_script = match &options.restore_script {
+        Some(path) => Some(create_restore_script(path)?),
+        None => None,
+    };
+    for (branch, status) in to_delete {
+        let remote = if options.default_remote_from_upstream {
+            upstream_remote(&branch).unwrap_or_else(|| options.remote.clone())
+        } else {
+            options.remote.clone()
+        };
+        if !options.force {
+            let upstream = format!("{}/{}", remote, branch.name);
+            if let Some(ahead) = commits_ahead_of(&upstream, &branch.name)?.filter(|&n| n > 0) {
+                warn!(
+                    "Refusing to delete {}: it has {} commit(s) that aren't on {}. Use --force to delete anyway",
+                    branch.name, ahead, upstream
+                );
+                skipped.push((SkipReason::Ahead, branch.name.to_string()));
+                continue;
+            }
+        }
+        if !options.yes && !confirm(&confirmation_prompt(&branch, status.pull_request())) {
+            skipped.push((SkipReason::Declined, branch.name.to_string()));
+            continue;
+        }
+        if !delete_branch(&branch, options.delete_command.as_deref())? {
+            skipped.push((SkipReason::Failed, branch.name.to_string()));
+            continue;
+        }
+        // Written right away, so it's there even when the run gets interrupted
+        match &mut restore_script {
+            Some(file) => writeln!(file, "{}", restore_command(&branch))?,
+            None if !options.quiet => eprintln!("  restore with: {}", restore_command(&branch)),
+            None => {}
+        }
+        if !options.delete_remote {
+            continue;
+        }
+        // Remote deletions need their own confirmation, `--yes` alone isn't enough
+        if has_remote_branch(&remote, &branch)?
+            && (options.yes_remote || confirm(&remote_confirmation_prompt(&remote, &branch)))
+        {
+            delete_remote_branch(&remote, &branch)?;
+        }
+    }
+
+    // An empty stdout looks like a failure, or trips up `xargs`
+    let empty = merged_count == 0 && !options.count_only;
+    if empty && !options.quiet && !options.no_empty_message {
+        eprintln!("No merged branches to clean up");
+    }
+
+    if options.prune_tracking {
+        for tracking_ref in prune_tracking_refs(&options.remote)? {
+            if !options.quiet {
+                eprintln!("Pruned {}", tracking_ref);
+            }
+        }
+    }
+
+    log_skipped(skipped, total);
+
+    trace!(
+        "> Timing: local branches {:?}, remote branches {:?}, classifying {} branches {:?}, deleting {:?}",
+        local_elapsed,
+        remote_elapsed,
+        total,
+        detection_elapsed,
+        deletion_started.elapsed()
+    );
+    if options.stats {
+        stats.classifying = detection_elapsed;
+        stats.deleting = deletion_started.elapsed();
+        stats.total = run_started.elapsed();
+        print_stats(stats, options.format);
+    }
+
+    if !required_unmerged.is_empty() {
+        error!(
+            "These branches aren't merged yet: {}",
+            required_unmerged.join(", ")
+        );
+        exit(1);
+    }
+    if options.exit_code && merged_count == 0 {
+        exit(1);
+    }
+    Ok(())
+}
+
+/// `(merged #12, OPEN #45)`, for merged branches that have open PRs too
+fn open_pr_annotation(status: &MergeStatus, open_pull_requests: &[PullRequest]) -> Option<String> {
+    if open_pull_requests.is_empty() {
+        return None;
+    }
+    let merged = status
+        .pull_requests()
+        .iter()
+        .map(|pr| format!("merged {}", pr.number));
+    let open = open_pull_requests
+        .iter()
+        .map(|pr| format!("OPEN {}", pr.number));
+    Some(format!(
+        "({})",
+        merged.chain(open).collect::<Vec<_>>().join(", ")
+    ))
+}
+
+/// `open_pull_requests` is `None` unless `--annotate-open-pr` is given, so
+/// the list stays usable as plain branch names otherwise
+fn print_text(
+    evaluated: Vec<(Branch, MergeStatus)>,
+    options: &Options,
+    open_pull_requests: Option<&std::collections::HashMap<String, Vec<PullRequest>>>,
+    highlight: bool,
+) -> std::io::Result<()> {
+    let mut merged: Vec<_> = evaluated
+        .into_iter()
+        .filter(|(_, status)| status.via().is_some())
+        .collect();
+    let group_by_strategy = options.group_by == Some(GroupBy::Strategy);
+    if group_by_strategy {
+        merged.sort_by_key(|(_, status)| status.via());
+    }
+    // Buffered, but flushed every few lines so whatever reads a pipe keeps up
+    write_text(
+        BufWriter::new(stdout().lock()),
+        &merged,
+        group_by_strategy,
+        open_pull_requests,
+        highlight,
+    )?;
+
+    let printed: Vec<String> = merged.into_iter().map(|(branch, _)| branch.name).collect();
+    if !options.quiet && stderr().is_terminal() {
+        if let Some(hint) = next_step_hint(&printed) {
+            eprintln!("{}", hint.dimmed());
+        }
+    }
+    Ok(())
+}
+
+/// Writes the merged branches, flushing `out` every `FLUSH_EVERY` lines
+fn write_text<W: Write>(
+    mut out: W,
+    merged: &[(Branch, MergeStatus)],
+    group_by_strategy: bool,
+    open_pull_requests: Option<&std::collections::HashMap<String, Vec<PullRequest>>>,
+    highlight: bool,
+) -> std::io::Result<()> {
+    let mut current_group = None;
+    for (index, (branch, status)) in merged.iter().enumerate() {
+        if group_by_strategy && current_group != status.via() {
+            current_group = status.via();
+            if let Some(via) = current_group {
+                writeln!(out, "# {}", via)?;
+            }
+        }
+        let name = match status {
+            MergeStatus::StaleMerged { .. } if highlight => branch.name.yellow(),
+            _ => branch.name.normal(),
+        };
+        let annotation = open_pull_requests
+            .and_then(|open| open.get(&branch.name))
+            .and_then(|open| open_pr_annotation(status, open));
+        match annotation {
+            Some(annotation) if highlight => writeln!(out, "{} {}", name, annotation.bold())?,
+            Some(annotation) => writeln!(out, "{} {}", name, annotation)?,
+            None => writeln!(out, "{}", name)?,
+        }
+        if (index + 1) % FLUSH_EVERY == 0 {
+            out.flush()?;
+        }
+    }
+    out.flush()
+}
+
+/// Whether the locale can show `✓` and friends, going by the variables
+/// that decide it in the order libc checks them
+fn is_utf8_locale() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+fn status_symbol(status: &MergeStatus, unicode: bool) -> &'static str {
+    match (status, unicode) {
+        (MergeStatus::Merged { .. } | MergeStatus::StaleMerged { .. }, true) => "✓",
+        (MergeStatus::Merged { .. } | MergeStatus::StaleMerged { .. }, false) => "+",
+        (MergeStatus::Diverged { .. }, true) => "≠",
+        (MergeStatus::Diverged { .. }, false) => "~",
+        (MergeStatus::MergedWithCommitsOnTop { .. }, true) => "↑",
+        (MergeStatus::MergedWithCommitsOnTop { .. }, false) => "^",
+        (MergeStatus::NotFound, _) => "?",
+        (MergeStatus::Protected(_), _) => "-",
+    }
+}
+
+/// `--oneline`: `<symbol> <name> <short hash> <pr>` for every branch
+fn oneline(
+    branch: &Branch,
+    status: &MergeStatus,
+    abbrev: usize,
+    unicode: bool,
+    color: bool,
+) -> String {
+    let symbol = status_symbol(status, unicode);
+    let symbol = match status {
+        _ if !color => symbol.normal(),
+        MergeStatus::Merged { .. } => symbol.green(),
+        MergeStatus::StaleMerged { .. }
+        | MergeStatus::Diverged { .. }
+        | MergeStatus::MergedWithCommitsOnTop { .. } => symbol.yellow(),
+        MergeStatus::NotFound | MergeStatus::Protected(_) => symbol.dimmed(),
+    };
+    let pull_request = status.pull_request().map_or("-", |pr| pr.number.as_str());
+    let line = format!(
+        "{} {} {} {}",
+        symbol,
+        branch.name,
+        abbreviate(&branch.commit_hash, abbrev),
+        pull_request
+    );
+    match branch.divergence {
+        Some(divergence) => format!("{} +{}/-{}", line, divergence.ahead, divergence.behind),
+        None => line,
+    }
+}
+
+fn print_oneline(
+    evaluated: &[(Branch, MergeStatus)],
+    abbrev: usize,
+    color: bool,
+) -> std::io::Result<()> {
+    let unicode = is_utf8_locale();
+    let mut out = BufWriter::new(stdout().lock());
+    for (index, (branch, status)) in evaluated.iter().enumerate() {
+        writeln!(out, "{}", oneline(branch, status, abbrev, unicode, color))?;
+        if (index + 1) % FLUSH_EVERY == 0 {
+            out.flush()?;
+        }
+    }
+    out.flush()
+}
+
+/// Quotes a CSV field when it contains a comma, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(branch: &Branch, status: &MergeStatus) -> String {
+    let pull_request = status.pull_request().map(|pr| pr.number.as_str());
+    let bases = status.bases().join(";");
+    [
+        branch.name.as_str(),
+        branch.commit_hash.as_str(),
+        status.label(),
+        pull_request.unwrap_or(""),
+        bases.as_str(),
+        branch.committed_date.as_deref().unwrap_or(""),
+        branch.author.as_deref().unwrap_or(""),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Unlike the text format, every evaluated branch gets a row
+fn print_csv(evaluated: &[(Branch, MergeStatus)], with_divergence: bool) {
+    if with_divergence {
+        println!("name,commit,status,pr,bases,date,author,ahead,behind");
+    } else {
+        println!("name,commit,status,pr,bases,date,author");
+    }
+    for (branch, status) in evaluated {
+        match (with_divergence, branch.divergence) {
+            (false, _) => println!("{}", csv_row(branch, status)),
+            (true, Some(divergence)) => println!(
+                "{},{},{}",
+                csv_row(branch, status),
+                divergence.ahead,
+                divergence.behind
+            ),
+            (true, None) => println!("{},,", csv_row(branch, status)),
+        }
+    }
+}
+
+/// The same fields as a CSV row, and the divergence when there is one
+fn branch_json(branch: &Branch, status: &MergeStatus) -> String {
+    let bases: Vec<String> = status
+        .bases()
+        .iter()
+        .map(|base| json_string(base))
+        .collect();
+    let divergence = match branch.divergence {
+        Some(divergence) => format!(
+            "{{\"ahead\":{},\"behind\":{}}}",
+            divergence.ahead, divergence.behind
+        ),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"name\":{},\"commit\":{},\"status\":{},\"pr\":{},\"bases\":[{}],\"date\":{},\"author\":{},\"divergence\":{}}}",
+        json_string(&branch.name),
+        json_string(&branch.commit_hash),
+        json_string(status.label()),
+        json_option(status.pull_request().map(|pr| json_string(&pr.number))),
+        bases.join(","),
+        json_option(branch.committed_date.as_deref().map(json_string)),
+        json_option(branch.author.as_deref().map(json_string)),
+        divergence
+    )
+}
+
+/// Like the CSV format, every evaluated branch gets an entry
+fn print_json(evaluated: &[(Branch, MergeStatus)]) {
+    let branches: Vec<String> = evaluated
+        .iter()
+        .map(|(branch, status)| branch_json(branch, status))
+        .collect();
+    println!("[{}]", branches.join(","));
+}
+
+/// Escapes the message of a workflow command
+fn escape_workflow_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn github_actions_annotation(branch: &Branch, status: &MergeStatus) -> Option<String> {
+    let pull_request = status
+        .pull_request()
+        .map(|pr| format!(" in {}", pr.number))
+        .unwrap_or_default();
+    let (level, message) = match status {
+        MergeStatus::Merged { .. } => (
+            "notice",
+            format!("{} was merged{}", branch.name, pull_request),
+        ),
+        MergeStatus::StaleMerged { age_days, .. } => (
+            "notice",
+            format!(
+                "{} was merged{} and its last commit is {} days old",
+                branch.name, pull_request, age_days
+            ),
+        ),
+        MergeStatus::Diverged { pull_request } => (
+            "warning",
+            format!(
+                "{} matches merged PR {} but its commit differs",
+                branch.name, pull_request.number
+            ),
+        ),
+        MergeStatus::MergedWithCommitsOnTop { commits_on_top, .. } => (
+            "warning",
+            format!(
+                "{} was merged{}, with {} commit(s) on top",
+                branch.name, pull_request, commits_on_top
+            ),
+        ),
+        MergeStatus::NotFound => ("notice", format!("{} isn't merged", branch.name)),
+        MergeStatus::Protected(_) => return None,
+    };
+    Some(format!("::{}::{}", level, escape_workflow_data(&message)))
+}
+
+fn print_github_actions(evaluated: &[(Branch, MergeStatus)], merged_count: usize) {
+    for (branch, status) in evaluated {
+        if let Some(annotation) = github_actions_annotation(branch, status) {
+            println!("{}", annotation);
+        }
+    }
+    println!(
+        "::notice title=merged_branches::{} of {} branches are merged",
+        merged_count,
+        evaluated.len()
+    );
+}
+
+/// `RUST_LOG` takes precedence over the `-v` and `-q` flags
+fn log_level(verbosity: u8, quiet: bool) -> log::LevelFilter {
+    match verbosity {
+        _ if quiet => log::LevelFilter::Error,
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
+
+fn init_logger(verbosity: u8, quiet: bool) {
+    env_logger::Builder::new()
+        .filter_level(log_level(verbosity, quiet))
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            log::Level::Error => writeln!(buf, "{}", format!("error: {}", record.args()).red()),
+            log::Level::Warn => writeln!(buf, "{}", format!("warning: {}", record.args()).yellow()),
+            _ => writeln!(buf, "{}", format!("{}", record.args()).dimmed()),
+        })
+        .init();
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1246 @@
+//! Runs the binary against scratch repositories and input files
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A fresh directory for one test, named after it
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("merged-branches-tests")
+        .join(format!("{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Can't create a scratch directory");
+    dir
+}
+
+fn write(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).expect("Can't write a test file");
+    path.to_string_lossy().to_string()
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_merged_branches_cli"))
+        .current_dir(dir)
+        .args(args)
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Can't run merged_branches_cli")
+}
+
+/// Like `run`, with `answers` for the confirmation prompts on stdin
+fn run_answering(dir: &Path, args: &[&str], answers: &str) -> Output {
+    use std::io::Write;
+    use std::process::Stdio;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_merged_branches_cli"))
+        .current_dir(dir)
+        .args(args)
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("RUST_LOG")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Can't run merged_branches_cli");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(answers.as_bytes()).unwrap();
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+const C: &str = "cccccccccccccccccccccccccccccccccccccccc";
+const D: &str = "dddddddddddddddddddddddddddddddddddddddd";
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("Can't run git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        stderr(&output)
+    );
+    stdout(&output).trim().to_string()
+}
+
+/// A repository on `master` with one commit
+fn repository(name: &str) -> PathBuf {
+    let dir = scratch(name);
+    git(&dir, &["init", "-q", "-b", "master"]);
+    git(&dir, &["config", "user.name", "Test"]);
+    git(&dir, &["config", "user.email", "test@example.com"]);
+    git(&dir, &["config", "commit.gpgsign", "false"]);
+    commit(&dir, "Initial commit");
+    dir
+}
+
+fn commit(dir: &Path, message: &str) -> String {
+    git(dir, &["commit", "-q", "--allow-empty", "-m", message]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// A `PATH` with git and sh, but none of the provider CLIs
+#[cfg(unix)]
+fn path_without_providers(dir: &Path) -> PathBuf {
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    for program in ["git", "sh"] {
+        let output = Command::new("sh")
+            .args(["-c", &format!("command -v {}", program)])
+            .output()
+            .unwrap();
+        let path = stdout(&output).trim().to_string();
+        let _ = std::os::unix::fs::symlink(path, bin.join(program));
+    }
+    bin
+}
+
+#[cfg(unix)]
+fn run_without_providers(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_merged_branches_cli"))
+        .current_dir(dir)
+        .args(args)
+        .env("PATH", path_without_providers(dir))
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Can't run merged_branches_cli")
+}
+
+#[test]
+#[cfg(unix)]
+fn fallback_never_lists_the_default_branch() {
+    let dir = repository("fallback-default-branch");
+    // Fast-forward merged, so it points where master does
+    git(&dir, &["branch", "feature"]);
+    git(&dir, &["checkout", "-q", "-b", "wip"]);
+    commit(&dir, "Work in progress");
+
+    let output = run_without_providers(&dir, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+    assert!(stderr(&output).contains("origin/HEAD isn't set"));
+
+    let output = run_without_providers(&dir, &["--explain", "master"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("master is the default branch"));
+}
+
+/// Puts an executable `name` running `script` on the `PATH` of `run_without_providers`
+#[cfg(unix)]
+fn fake_provider(dir: &Path, name: &str, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    let path = path_without_providers(dir).join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn calls(dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(dir.join("calls.txt"))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Prints as many unrelated merged PRs as `--limit` asks for
+#[cfg(unix)]
+const FAKE_HUB: &str = r#"
+if [ "$1" = "--version" ]; then echo "hub version 2.14.2"; exit 0; fi
+echo "$@" >> calls.txt
+limit=0
+while [ "$#" -gt 0 ]; do
+  if [ "$1" = "--limit" ]; then limit=$2; fi
+  shift
+done
+i=0
+while [ "$i" -lt "$limit" ]; do
+  echo "merged #$i other/$i 000000000000000000000000000000000000000$((i % 10)) - master Other"
+  i=$((i + 1))
+done
+"#;
+
+#[test]
+#[cfg(unix)]
+fn auto_limit_stops_when_doubling_matches_nothing_new() {
+    let dir = repository("auto-limit-no-progress");
+    git(&dir, &["branch", "wip"]);
+    git(&dir, &["checkout", "-q", "wip"]);
+    commit(&dir, "Work in progress");
+    git(&dir, &["checkout", "-q", "master"]);
+    fake_provider(&dir, "hub", FAKE_HUB);
+
+    let output = run_without_providers(&dir, &["--remote-limit-auto", "--limit", "2"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let calls = calls(&dir);
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls[0].ends_with("--limit 2"));
+    assert!(calls[1].ends_with("--limit 4"));
+}
+
+#[test]
+#[cfg(unix)]
+fn auto_limit_doubles_until_every_branch_is_matched() {
+    let dir = repository("auto-limit-match");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["checkout", "-q", "master"]);
+    // The feature's PR is the 4th newest, so only `--limit 4` reaches it
+    let script = format!(
+        r#"
+if [ "$1" = "--version" ]; then echo "hub version 2.14.2"; exit 0; fi
+echo "$@" >> calls.txt
+limit=0
+while [ "$#" -gt 0 ]; do
+  if [ "$1" = "--limit" ]; then limit=$2; fi
+  shift
+done
+i=0
+while [ "$i" -lt "$limit" ]; do
+  if [ "$i" -eq 3 ]; then
+    echo "merged #3 feature {} - master Feature"
+  else
+    echo "merged #$i other/$i 000000000000000000000000000000000000000$i - master Other"
+  fi
+  i=$((i + 1))
+done
+"#,
+        tip
+    );
+    fake_provider(&dir, "hub", &script);
+
+    let output = run_without_providers(&dir, &["--remote-limit-auto", "--limit", "2"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+    let calls = calls(&dir);
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls[0].ends_with("--limit 2"));
+    assert!(calls[1].ends_with("--limit 4"));
+}
+
+#[test]
+#[cfg(unix)]
+fn auto_limit_doesnt_rerun_provider_commands() {
+    let dir = repository("auto-limit-command");
+    git(&dir, &["branch", "wip"]);
+    let output = run_without_providers(
+        &dir,
+        &[
+            "--remote-limit-auto",
+            "--limit",
+            "1",
+            "--provider-command",
+            "echo call >> calls.txt; echo 'merged #1 a 0000000000000000000000000000000000000000 - master A'",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(calls(&dir).len(), 1);
+}
+
+#[test]
+fn branches_sharing_a_tip_are_both_merged_and_deleted_in_order() {
+    let dir = repository("shared-tip");
+    git(&dir, &["checkout", "-q", "-b", "later"]);
+    let tip = commit(&dir, "Shared work");
+    git(&dir, &["branch", "earlier"]);
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 later {} - master Shared\n", tip),
+    );
+
+    let output = run(&dir, &["--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "earlier\nlater\n");
+
+    let output = run_answering(&dir, &["--pr-input", &prs, "--delete"], "y\ny\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    let deleted: Vec<&str> = printed
+        .lines()
+        .filter_map(|line| line.strip_prefix("Deleted branch "))
+        .filter_map(|rest| rest.split(' ').next())
+        .collect();
+    assert_eq!(deleted, vec!["earlier", "later"]);
+    assert_eq!(git(&dir, &["branch", "--list", "earlier", "later"]), "");
+}
+
+#[test]
+#[cfg(unix)]
+fn offline_runs_never_spawn_a_provider() {
+    let dir = repository("offline");
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["branch", "feature"]);
+    for provider in ["hub", "gh"] {
+        fake_provider(&dir, provider, "echo \"$0 $@\" >> calls.txt; exit 1");
+    }
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature {} - master Feature\n", tip),
+    );
+
+    let output = run_without_providers(&dir, &["--offline", "--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+    assert_eq!(calls(&dir), Vec::<String>::new());
+
+    let output = run_without_providers(&dir, &["--offline"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--offline can't fetch PRs"));
+    assert_eq!(calls(&dir), Vec::<String>::new());
+}
+
+#[test]
+fn both_inputs_from_files_classify_without_a_repository() {
+    // Not a git repository, so any git call would fail and warn
+    let dir = scratch("file-inputs");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!(
+            "feature/merged {} 1600000000\nfeature/diverged {}\nfeature/unknown {}\n",
+            A, B, D
+        ),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature/merged {} - master Merged\nmerged #2 feature/diverged {} - master Diverged\n",
+            A, C
+        ),
+    );
+    let output = run(&dir, &["--local-input", &local, "--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature/merged\n");
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "warning: feature/diverged matches merged PR #2 but commit differs ({})\n",
+            &B[..7]
+        )
+    );
+
+    let output = run(&dir, &["--local-input", &local, "--pr-input", &prs, "-v"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let progress = stderr(&output);
+    assert!(progress.contains("> Done collecting local branches from file!"));
+    assert!(progress.contains("> Done collecting remote branches from file!"));
+    assert!(!progress.contains("from git!") && !progress.contains("from GitHub!"));
+}
+
+/// A bare repository `name` next to `dir`, added to it as the remote `name`
+fn bare_remote(dir: &Path, name: &str) -> PathBuf {
+    let remote = dir.with_file_name(format!(
+        "{}-{}",
+        dir.file_name().unwrap().to_string_lossy(),
+        name
+    ));
+    let _ = std::fs::remove_dir_all(&remote);
+    git(
+        dir.parent().unwrap(),
+        &["init", "-q", "--bare", &remote.to_string_lossy()],
+    );
+    git(dir, &["remote", "add", name, &remote.to_string_lossy()]);
+    remote
+}
+
+#[test]
+fn prune_tracking_removes_refs_of_deleted_remote_branches() {
+    let dir = repository("prune-tracking");
+    let remote = bare_remote(&dir, "origin");
+    git(&dir, &["branch", "gone"]);
+    git(&dir, &["push", "-q", "origin", "master", "gone"]);
+    git(&remote, &["branch", "-D", "gone"]);
+    let prs = write(&dir, "prs.txt", "");
+
+    let output = run(&dir, &["--pr-input", &prs, "--prune-tracking"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Pruned origin/gone"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(git(&dir, &["branch", "--remotes"]), "origin/master");
+
+    // Nothing left to prune
+    let output = run(&dir, &["--pr-input", &prs, "--prune-tracking"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Pruned"), "{}", stderr(&output));
+}
+
+#[test]
+fn committing_after_the_merge_is_reported_as_diverged() {
+    let dir = repository("diverged");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    let merged = commit(&dir, "Merged work");
+    commit(&dir, "After the merge");
+    git(
+        &dir,
+        &["checkout", "-q", "-b", "behind", &format!("{}~1", merged)],
+    );
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature {} - master Feature\nmerged #2 behind {} - master Behind\n",
+            merged, merged
+        ),
+    );
+
+    let output = run(&dir, &["--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    // An ancestor of its PR's head is merged, one with commits on top isn't
+    assert_eq!(stdout(&output), "behind\n");
+    assert!(
+        stderr(&output).contains("feature matches merged PR #1 but commit differs"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn branches_ahead_of_their_remote_are_only_deleted_with_force() {
+    let dir = repository("ahead-of-remote");
+    bare_remote(&dir, "origin");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    commit(&dir, "Pushed");
+    git(&dir, &["push", "-q", "origin", "feature"]);
+    let tip = commit(&dir, "Not pushed");
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature {} - master Feature\n", tip),
+    );
+
+    let output = run_answering(&dir, &["--pr-input", &prs, "--delete"], "y\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains(
+            "Refusing to delete feature: it has 1 commit(s) that aren't on origin/feature"
+        ),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(git(&dir, &["rev-parse", "feature"]), tip);
+
+    let output = run_answering(&dir, &["--pr-input", &prs, "--delete", "--force"], "y\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&dir, &["branch", "--list", "feature"]), "");
+}
+
+#[test]
+fn grouping_by_strategy_puts_exact_matches_first() {
+    let dir = repository("group-by-strategy");
+    git(&dir, &["checkout", "-q", "-b", "c-exact"]);
+    let first = commit(&dir, "First");
+    let second = commit(&dir, "Second");
+    git(&dir, &["branch", "b-behind", &first]);
+    git(&dir, &["checkout", "-q", "-b", "a-merge-commit", &first]);
+    let merge_commit = commit(&dir, "Merge a");
+    git(&dir, &["checkout", "-q", "-b", "d-unmerged"]);
+    commit(&dir, "Unmerged");
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 c-exact {} - master C\n\
+             merged #2 b-behind {} - master B\n\
+             merged #3 a-merge-commit {} {} master A\n",
+            second, second, A, merge_commit
+        ),
+    );
+
+    let output = run(&dir, &["--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "a-merge-commit\nb-behind\nc-exact\n");
+
+    let output = run(&dir, &["--pr-input", &prs, "--group-by", "strategy"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "# exact-hash\na-merge-commit\nc-exact\n# ancestor\nb-behind\n"
+    );
+}
+
+#[test]
+fn yes_alone_never_deletes_remote_branches() {
+    let dir = repository("yes-remote");
+    let remote = bare_remote(&dir, "origin");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["push", "-q", "origin", "feature"]);
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature {} - master Feature\n", tip),
+    );
+
+    // Nobody answers the prompt, which counts as a no
+    let output = run(
+        &dir,
+        &["--pr-input", &prs, "--delete", "--delete-remote", "--yes"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("on the remote `origin`? This affects everyone using it"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(git(&dir, &["branch", "--list", "feature"]), "");
+    assert_eq!(git(&remote, &["rev-parse", "feature"]), tip);
+
+    git(&dir, &["branch", "feature", &tip]);
+    let output = run(
+        &dir,
+        &[
+            "--pr-input",
+            &prs,
+            "--delete",
+            "--delete-remote",
+            "--yes",
+            "--yes-remote",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&remote, &["branch", "--list", "feature"]), "");
+}
+
+#[test]
+fn required_branches_that_arent_merged_fail_the_run() {
+    let dir = scratch("fail-on-unmerged");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!(
+            "release/1.0 {}\nrelease/1.1 {}\nfeature/other {}\n",
+            A, B, C
+        ),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 release/1.0 {} - master Release\n", A),
+    );
+    let args = ["--local-input", &local, "--pr-input", &prs, "--offline"];
+
+    let mut failing = args.to_vec();
+    failing.extend(["--fail-on-unmerged", "release/*"]);
+    let output = run(&dir, &failing);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "release/1.0\n");
+    assert!(
+        stderr(&output).contains("These branches aren't merged yet: release/1.1"),
+        "{}",
+        stderr(&output)
+    );
+
+    let mut passing = args.to_vec();
+    passing.extend(["--fail-on-unmerged", "release/1.0"]);
+    let output = run(&dir, &passing);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn branches_in_the_keep_file_are_never_reported() {
+    let dir = repository("keep-file");
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["branch", "release/1.0"]);
+    git(&dir, &["branch", "feature"]);
+    write(&dir, ".merged-branches-keep", "# Kept\nrelease/*\n");
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 release/1.0 {} - master Release\nmerged #2 feature {} - master Feature\n",
+            tip, tip
+        ),
+    );
+
+    let output = run(&dir, &["--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+
+    let output = run(&dir, &["--pr-input", &prs, "--delete", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&dir, &["branch", "--list", "release/*"]), "release/1.0");
+}
+
+#[test]
+fn neither_the_base_nor_a_branch_created_from_it_is_merged() {
+    let dir = scratch("merge-commit-candidates");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nmaster {}\nnew-branch {}\n", B, B, B),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature/a {} {} master Add a\nopen #2 new-branch {} - master New\n",
+            A, B, B
+        ),
+    );
+    let output = run(&dir, &["--local-input", &local, "--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature/a\n");
+}
+
+#[test]
+fn branches_outside_the_scope_are_never_reported() {
+    let dir = scratch("scope");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nfix/b {}\nfeature/c {}\n", A, B, C),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature/a {} - master A\nmerged #2 fix/b {} - master B\n",
+            A, B
+        ),
+    );
+    let args = [
+        "--local-input",
+        &local,
+        "--pr-input",
+        &prs,
+        "--scope",
+        "feature/*",
+        "-v",
+    ];
+
+    let output = run(&dir, &args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature/a\n");
+    assert!(!stderr(&output).contains("fix/b"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("feature/c"), "{}", stderr(&output));
+
+    let mut csv = args.to_vec();
+    csv.extend(["--format", "csv"]);
+    let output = run(&dir, &csv);
+    assert!(!stdout(&output).contains("fix/b"), "{}", stdout(&output));
+
+    let mut count = args.to_vec();
+    count.push("--count-only");
+    let output = run(&dir, &count);
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn fetching_the_whole_limit_warns_about_missing_prs() {
+    let dir = repository("saturated-limit");
+    git(&dir, &["branch", "feature"]);
+    fake_provider(&dir, "hub", FAKE_HUB);
+
+    let output = run_without_providers(&dir, &["--limit", "3"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Got exactly 3 PRs, which is the limit"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn provider_commands_are_read_like_pr_input() {
+    let dir = scratch("provider-command");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nfeature/b {}\n", A, B),
+    );
+    let command = format!("echo 'merged #1 feature/a {} - master A'", A);
+    let output = run(
+        &dir,
+        &["--local-input", &local, "--provider-command", &command],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature/a\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn provider_output_that_isnt_utf8_is_skipped() {
+    let dir = scratch("provider-command-utf8");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nfeature/b {}\n", A, B),
+    );
+    let command = format!(
+        "printf 'merged #1 feature/\\377 {} - master Bad\\nmerged #2 feature/b {} - master B\\n'",
+        A, B
+    );
+    let output = run(
+        &dir,
+        &["--local-input", &local, "--provider-command", &command],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature/b\n");
+}
+
+#[test]
+fn bare_clones_read_the_keep_file_from_the_git_directory() {
+    let dir = repository("bare-clone");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["branch", "release/1.0"]);
+    git(&dir, &["checkout", "-q", "master"]);
+    let bare = bare_remote(&dir, "origin");
+    git(&dir, &["push", "-q", "origin", "--all"]);
+    write(&bare, ".merged-branches-keep", "release/*\n");
+    let prs = write(
+        &bare,
+        "prs.txt",
+        &format!(
+            "merged #1 feature {} - master Feature\nmerged #2 release/1.0 {} - master Release\n",
+            tip, tip
+        ),
+    );
+
+    // Even from inside the git directory
+    let output = run(&bare.join("refs"), &["--pr-input", &prs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+
+    let output = run(&bare, &["--pr-input", &prs, "--delete", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&bare, &["branch", "--list", "feature"]), "");
+    assert_eq!(
+        git(&bare, &["branch", "--list", "release/*"]),
+        "release/1.0"
+    );
+}
+
+#[test]
+fn progress_is_logged_at_the_expected_cadence() {
+    let dir = scratch("progress");
+    let branches: String = (0..25)
+        .map(|index| format!("feature/{:02} {:040x}\n", index, index))
+        .collect();
+    let local = write(&dir, "local.txt", &branches);
+    let prs = write(&dir, "prs.txt", "");
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "-v",
+            "--progress-every",
+            "10",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let progress: Vec<String> = stderr(&output)
+        .lines()
+        .filter(|line| line.contains("> Processed"))
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(
+        progress,
+        vec!["> Processed 10/25 branches", "> Processed 20/25 branches"]
+    );
+}
+
+#[test]
+fn hashes_are_as_long_as_core_abbrev_says() {
+    let dir = repository("core-abbrev");
+    git(&dir, &["config", "core.abbrev", "12"]);
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["checkout", "-q", "master"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature {} - master Feature\n", tip),
+    );
+
+    let output = run(&dir, &["--pr-input", &prs, "--oneline"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains(&format!(" {} ", &tip[..12])),
+        "{}",
+        stdout(&output)
+    );
+    assert!(!stdout(&output).contains(&tip[..13]), "{}", stdout(&output));
+
+    let output = run(&dir, &["--pr-input", &prs, "--oneline", "--abbrev", "8"]);
+    assert!(stdout(&output).contains(&format!(" {} ", &tip[..8])));
+    assert!(!stdout(&output).contains(&tip[..9]), "{}", stdout(&output));
+}
+
+#[test]
+fn only_branches_with_the_label_are_deleted() {
+    let dir = repository("delete-if-label");
+    let labeled = commit(&dir, "Labeled work");
+    git(&dir, &["branch", "labeled"]);
+    let unlabeled = commit(&dir, "Unlabeled work");
+    git(&dir, &["branch", "unlabeled"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 labeled {} - master Labeled\tbug,Safe-To-Delete\nmerged #2 unlabeled {} - master Unlabeled\tbug\n",
+            labeled, unlabeled
+        ),
+    );
+
+    let output = run(
+        &dir,
+        &[
+            "--pr-input",
+            &prs,
+            "--delete",
+            "--yes",
+            "--delete-if-label",
+            "safe-to-delete",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("unlabeled\n"),
+        "{}",
+        stdout(&output)
+    );
+    assert_eq!(
+        git(&dir, &["branch", "--list", "labeled", "unlabeled"]),
+        "unlabeled"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn no_remote_never_runs_a_provider() {
+    let dir = repository("no-remote");
+    git(&dir, &["branch", "feature"]);
+    for provider in ["hub", "gh", "glab"] {
+        fake_provider(&dir, provider, "echo \"$0 $@\" >> calls.txt; exit 1");
+    }
+
+    let output = run_without_providers(&dir, &["--no-remote", "--merged-into", "master"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "feature\n");
+    assert_eq!(calls(&dir), Vec::<String>::new());
+
+    let output = run_without_providers(
+        &dir,
+        &[
+            "--no-remote",
+            "--merged-into",
+            "master",
+            "--provider-command",
+            "echo call >> calls.txt",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(calls(&dir), Vec::<String>::new());
+}
+
+#[test]
+fn timings_are_only_logged_at_trace_level() {
+    let dir = scratch("timings");
+    let local = write(&dir, "local.txt", &format!("feature/a {}\n", A));
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature/a {} - master A\n", A),
+    );
+    let args = ["--local-input", &local, "--pr-input", &prs];
+    for verbosity in ["-v", "-vv"] {
+        let mut verbose = args.to_vec();
+        verbose.push(verbosity);
+        let output = run(&dir, &verbose);
+        assert!(!stderr(&output).contains("> Timing"), "{}", stderr(&output));
+        assert!(!stderr(&output).contains("> Classified"));
+    }
+
+    let mut trace = args.to_vec();
+    trace.push("-vvv");
+    let output = run(&dir, &trace);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("> Classified feature/a in "),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("> Timing: local branches "));
+}
+
+#[test]
+fn never_merged_files_reject_anything_but_commit_hashes() {
+    let dir = scratch("never-merged-file");
+    let local = write(&dir, "local.txt", &format!("feature/a {}\n", A));
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature/a {} - master A\n", A),
+    );
+    let never_merged = write(&dir, "never-merged.txt", "# Too short\nab\n");
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "--never-merged-file",
+            &never_merged,
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("needs at least 4 characters of every commit, got ab"),
+        "{}",
+        stderr(&output)
+    );
+
+    let never_merged = write(&dir, "never-merged.txt", "release-1\n");
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "--never-merged-file",
+            &never_merged,
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("needs commit hashes, got release-1"),
+        "{}",
+        stderr(&output)
+    );
+
+    // Compared like git prints them, whatever the case in the file
+    let never_merged = write(&dir, "never-merged.txt", "AAAAAAA # Reverted\n");
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "--never-merged-file",
+            &never_merged,
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+#[cfg(unix)]
+fn restore_scripts_recreate_deleted_branches() {
+    let dir = repository("restore-script");
+    let tip = commit(&dir, "Merged work");
+    git(&dir, &["branch", "fix"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 fix {} - master Fix\n", tip),
+    );
+    let script = dir.join("restore.sh");
+    let output = run(
+        &dir,
+        &[
+            "--pr-input",
+            &prs,
+            "--delete",
+            "--yes",
+            "--restore-script",
+            &script.to_string_lossy(),
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&dir, &["branch", "--list", "fix"]), "");
+
+    let output = Command::new("sh")
+        .arg(&script)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&dir, &["rev-parse", "fix"]), tip);
+}
+
+#[test]
+fn remote_deletes_go_to_each_branch_upstream() {
+    let dir = repository("upstream-remote");
+    let origin = bare_remote(&dir, "origin");
+    let fork = bare_remote(&dir, "fork");
+    let tip = commit(&dir, "Merged work");
+    for name in ["on-origin", "on-fork", "no-upstream"] {
+        git(&dir, &["branch", name]);
+    }
+    git(&dir, &["push", "-q", "-u", "origin", "on-origin"]);
+    git(&dir, &["push", "-q", "-u", "fork", "on-fork"]);
+    // The same branch on both, without tracking either
+    git(&dir, &["push", "-q", "origin", "no-upstream"]);
+    git(&dir, &["push", "-q", "fork", "no-upstream", "on-origin"]);
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &["on-origin", "on-fork", "no-upstream"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| format!("merged #{} {} {} - master PR\n", index, name, tip))
+            .collect::<String>(),
+    );
+
+    let output = run(
+        &dir,
+        &[
+            "--pr-input",
+            &prs,
+            "--delete",
+            "--delete-remote",
+            "--yes",
+            "--yes-remote",
+            "--default-remote-from-upstream",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(git(&origin, &["branch", "--list"]), "");
+    // Only on-fork tracks the fork, so the others are still there
+    assert_eq!(
+        git(&fork, &["branch", "--list", "--format", "%(refname:short)"]),
+        "no-upstream\non-origin"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn tokens_reach_the_provider_but_never_the_logs() {
+    let dir = repository("token-env");
+    git(&dir, &["branch", "feature"]);
+    fake_provider(
+        &dir,
+        "hub",
+        r#"if [ "$1" = "--version" ]; then echo "hub version 2.14.2"; exit 0; fi
+echo "$GITHUB_TOKEN" > token.txt"#,
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_merged_branches_cli"))
+        .current_dir(&dir)
+        .args(["--token-env", "CI_TOKEN", "-vvv"])
+        .env("PATH", path_without_providers(&dir))
+        .env("CI_TOKEN", "very-secret-token")
+        .env_remove("GITHUB_TOKEN")
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("token.txt")).unwrap(),
+        "very-secret-token\n"
+    );
+    assert!(
+        stderr(&output).contains("Passing $CI_TOKEN to hub as $GITHUB_TOKEN"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("very-secret-token"));
+}
+
+#[test]
+fn divergence_from_a_reference_is_counted_both_ways() {
+    let dir = repository("merge-base-with");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    commit(&dir, "Feature 1");
+    let tip = commit(&dir, "Feature 2");
+    git(&dir, &["checkout", "-q", "master"]);
+    commit(&dir, "Master 1");
+    let output = run(
+        &dir,
+        &[
+            "--pr-input",
+            &write(&dir, "prs.txt", ""),
+            "--merge-base-with",
+            "master",
+            "--format",
+            "csv",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let row = stdout(&output)
+        .lines()
+        .find(|line| line.starts_with("feature,"))
+        .unwrap()
+        .to_string();
+    assert!(row.starts_with(&format!("feature,{},not-found,", tip)));
+    assert!(row.ends_with(",2,1"), "{}", row);
+}
+
+#[test]
+fn empty_results_explain_themselves_unless_asked_not_to() {
+    let dir = scratch("empty-results");
+    let local = write(&dir, "local.txt", &format!("feature/a {}\n", A));
+    let prs = write(&dir, "prs.txt", "");
+    let args = ["--local-input", local.as_str(), "--pr-input", prs.as_str()];
+    let with = |extra: &[&str]| {
+        let mut all = args.to_vec();
+        all.extend(extra);
+        run(&dir, &all)
+    };
+
+    let output = with(&[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("No merged branches to clean up"));
+
+    let output = with(&["--exit-code"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("No merged branches to clean up"));
+
+    let output = with(&["--no-empty-message"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+
+    let output = with(&["--quiet"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+
+    // Something to clean up passes
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature/a {} - master A\n", A),
+    );
+    let output = run(
+        &dir,
+        &["--local-input", &local, "--pr-input", &prs, "--exit-code"],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "feature/a\n");
+}
+
+#[test]
+fn json_lists_every_branch_on_its_own() {
+    let dir = scratch("json");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nfeature/b {}\n", A, B),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!("merged #1 feature/a {} - master A\n", A),
+    );
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "--format",
+            "json",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json = stdout(&output);
+    assert!(json.starts_with("[{\"name\":\"feature/a\""), "{}", json);
+    assert!(json.contains("{\"name\":\"feature/b\""), "{}", json);
+    assert!(json.contains("\"status\":\"not-found\""), "{}", json);
+}
+
+#[test]
+fn stats_are_printed_as_json() {
+    let dir = scratch("stats");
+    let local = write(
+        &dir,
+        "local.txt",
+        &format!("feature/a {}\nfeature/b {}\n", A, B),
+    );
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature/a {} - master A\nopen #2 feature/b {} - master B\n",
+            A, C
+        ),
+    );
+    let output = run(
+        &dir,
+        &[
+            "--local-input",
+            &local,
+            "--pr-input",
+            &prs,
+            "--stats",
+            "--format",
+            "json",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("[{\"name\":\"feature/a\""));
+    let stats = stderr(&output);
+    assert!(stats.contains("\"subprocesses\":0"), "{}", stats);
+    assert!(stats.contains("\"lines_parsed\":4"), "{}", stats);
+    assert!(
+        stats.contains("\"cache\":{\"commits\":1,\"names\":1,\"open_names\":1}"),
+        "{}",
+        stats
+    );
+    assert!(
+        stats.contains("\"timings_ms\":{\"local_branches\":"),
+        "{}",
+        stats
+    );
+}
+
+#[test]
+fn github_actions_keeps_the_text_list_for_text_only_flags() {
+    let dir = scratch("github-actions-text-only");
+    let local = write(&dir, "local.txt", &format!("feature/a {}\n", A));
+    let prs = write(
+        &dir,
+        "prs.txt",
+        &format!(
+            "merged #1 feature/a {} - master A\nopen #2 feature/a {} - master Again\n",
+            A, B
+        ),
+    );
+    for flag in ["--oneline", "--annotate-open-pr"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_merged_branches_cli"))
+            .current_dir(&dir)
+            .args(["--local-input", &local, "--pr-input", &prs, flag])
+            .env("GITHUB_ACTIONS", "true")
+            .env_remove("RUST_LOG")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {}", flag, stderr(&output));
+        assert!(!stdout(&output).contains("::"), "{}", stdout(&output));
+    }
+}